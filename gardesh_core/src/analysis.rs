@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::{normalize, Constraints, Place, PlaceType};
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct PlaceInfo {
+  pub place_type: PlaceType,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// How many places of this kind were asked for; one when unset.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub count: Option<u32>,
+  /// Where in the trip these places are visited (`اول یک خانه تاریخی، بعد ناهار`).
+  #[serde(default, skip_serializing_if = "StopOrder::is_flexible")]
+  pub order: StopOrder,
+}
+
+/// Ordering intent of a [`PlaceInfo`]. The stops of an info are visited one after another, and
+/// positions count stops: `دو موزه` at position 2 fills the second and third stops.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StopOrder {
+  /// Anywhere the route is shortest.
+  #[default]
+  Flexible,
+  /// Starting at this 1-based stop, or last when the plan has fewer stops.
+  Position {
+    #[serde(default)]
+    position: u32,
+  },
+  Last,
+  /// Before every info of `place_type`.
+  Before {
+    #[serde(default)]
+    place_type: PlaceType,
+  },
+  /// After every info of `place_type`.
+  After {
+    #[serde(default)]
+    place_type: PlaceType,
+  },
+}
+
+impl StopOrder {
+  pub fn is_flexible(&self) -> bool {
+    *self == StopOrder::Flexible
+  }
+
+  /// Whether an info whose stops are `stops` of `sequence`, the place types of a plan's stops in
+  /// visiting order, is where this order wants it. An info without stops is never out of place.
+  pub fn holds(&self, stops: Range<usize>, sequence: &[&PlaceType]) -> bool {
+    if stops.is_empty() {
+      return true;
+    }
+    match self {
+      StopOrder::Flexible => true,
+      StopOrder::Position { position } => match *position as usize {
+        position if position > sequence.len() => stops.end == sequence.len(),
+        position => stops.start + 1 == position.max(1),
+      },
+      StopOrder::Last => stops.end == sequence.len(),
+      StopOrder::Before { place_type } => sequence[..stops.start].iter().all(|f| *f != place_type),
+      StopOrder::After { place_type } => sequence[stops.end..].iter().all(|f| *f != place_type),
+    }
+  }
+}
+
+impl PlaceInfo {
+  /// Number of the place's tags requested by this info, or 0 when the place is of another type.
+  ///
+  /// Tags are compared after [`normalize`], so spelling variants of the same tag still match.
+  pub fn match_score(&self, place: &Place) -> usize {
+    if place.r#type != self.place_type {
+      return 0;
+    }
+    let wanted = self.tags.iter().map(|tag| normalize(tag)).collect::<HashSet<_>>();
+    place.tags.iter().filter(|tag| wanted.contains(&normalize(tag))).count()
+  }
+
+  pub fn matches(&self, place: &Place) -> bool {
+    self.match_score(place) > 0
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PromptAnalyses {
+  pub place_infos: Vec<PlaceInfo>,
+  /// Number of stops asked for in all, when the prompt names one (`سه جا`); the planner trims or
+  /// pads the per-info counts to it.
+  pub total_count: Option<u32>,
+  /// Where the trip starts, when the prompt says (`من در دربند هستم`): a place title or
+  /// neighbourhood as named by the user, resolved by [`crate::gazetteer::Gazetteer::resolve`].
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub start: Option<String>,
+  /// Time, price, diet and accessibility limits the prompt states (`ناهار ارزان گیاهی`,
+  /// `سه ساعت وقت دارم`); the planner filters candidates by them.
+  #[serde(default, skip_serializing_if = "Constraints::is_empty")]
+  pub constraints: Constraints,
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn holds_counts_stops() {
+    let (museum, restaurant) = (PlaceType::new("موزه"), PlaceType::new("رستوران"));
+    // Two museums, then a restaurant.
+    let sequence = [&museum, &museum, &restaurant];
+    let at = |position| StopOrder::Position { position };
+    assert!(at(1).holds(0..2, &sequence));
+    assert!(at(3).holds(2..3, &sequence));
+    assert!(!at(2).holds(2..3, &sequence));
+    assert!(at(9).holds(2..3, &sequence));
+    assert!(!at(9).holds(0..2, &sequence));
+    assert!(StopOrder::Last.holds(2..3, &sequence));
+    assert!(!StopOrder::Last.holds(0..2, &sequence));
+    assert!(StopOrder::Before { place_type: restaurant.clone() }.holds(0..2, &sequence));
+    assert!(!StopOrder::After { place_type: restaurant.clone() }.holds(0..2, &sequence));
+    assert!(StopOrder::After { place_type: museum.clone() }.holds(2..3, &sequence));
+  }
+
+  #[test]
+  fn infos_without_stops_always_hold() {
+    for order in [StopOrder::Position { position: 2 }, StopOrder::Last] {
+      assert!(order.holds(0..0, &[]));
+      assert!(order.holds(1..1, &[&PlaceType::new("موزه")]));
+    }
+  }
+}
@@ -0,0 +1,216 @@
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::conversation::{ChatMessage, PlanEdit};
+use crate::fixture::FixtureMode;
+use crate::prefilter::DEFAULT_PROMPT_TAGS;
+use crate::{PlaceCatalog, PlanStop, PromptAnalyses};
+
+/// Turns a user prompt into the place types and tags the planner searches the catalog for.
+///
+/// Implementations may call a remote model, so the result is a future; no `Send` bound is
+/// required because the wasm build runs on a single thread.
+pub trait PromptAnalyzer {
+  /// Short label for logs and reports, e.g. `gemini/gemini-1.5-flash-8b`.
+  fn name(&self) -> String;
+
+  fn analyze(
+    &self,
+    catalog: &PlaceCatalog,
+    prompt: &str,
+  ) -> impl Future<Output = Result<PromptAnalyses, AnalyzeError>>;
+
+  /// Reads a follow-up to a session that already has a plan as edits to `stops`, for
+  /// [`crate::conversation::apply_edits`]; `history` holds the session's earlier turns.
+  fn refine(
+    &self,
+    catalog: &PlaceCatalog,
+    history: &[ChatMessage],
+    stops: &[PlanStop],
+    follow_up: &str,
+  ) -> impl Future<Output = Result<Vec<PlanEdit>, AnalyzeError>>;
+}
+
+#[derive(Debug, Display, Error)]
+pub enum AnalyzeError {
+  #[display("{analyzer} is misconfigured: {message}")]
+  Config { analyzer: String, message: String },
+  #[display("{analyzer} request failed: {message}")]
+  Request { analyzer: String, message: String },
+  #[display("{analyzer} returned no text")]
+  EmptyResponse { analyzer: String },
+  #[display("{analyzer} returned an unreadable analysis: {source}")]
+  Parse { analyzer: String, source: serde_json::Error },
+  #[display("{analyzer} fixture unavailable: {message}")]
+  Fixture { analyzer: String, message: String },
+}
+
+/// Wire protocol of the model serving the analysis.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Display)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmProvider {
+  #[default]
+  #[display("gemini")]
+  Gemini,
+  /// Any server speaking OpenAI's `chat/completions`, such as OpenAI itself, Groq or a relay.
+  #[display("openai")]
+  #[serde(rename = "openai")]
+  OpenAiCompatible,
+  #[display("ollama")]
+  Ollama,
+}
+
+impl LlmProvider {
+  pub fn default_base_url(&self) -> &'static str {
+    match self {
+      LlmProvider::Gemini => "https://generativelanguage.googleapis.com/v1beta/",
+      LlmProvider::OpenAiCompatible => "https://api.openai.com/v1/",
+      LlmProvider::Ollama => "http://localhost:11434/v1/",
+    }
+  }
+
+  pub fn default_model(&self) -> &'static str {
+    match self {
+      LlmProvider::Gemini => "gemini-1.5-flash-8b",
+      LlmProvider::OpenAiCompatible => "gpt-4o-mini",
+      LlmProvider::Ollama => "qwen2.5:7b",
+    }
+  }
+
+  /// Environment variable the key is read from when the config leaves it empty.
+  pub fn api_key_env(&self) -> Option<&'static str> {
+    match self {
+      LlmProvider::Gemini => Some("GEMINI_API_KEY"),
+      LlmProvider::OpenAiCompatible => Some("OPENAI_API_KEY"),
+      LlmProvider::Ollama => None,
+    }
+  }
+}
+
+/// Which model analyzes prompts; stored as JSON so the provider can be switched without a rebuild.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct LlmConfig {
+  #[serde(default)]
+  pub provider: LlmProvider,
+  #[serde(default)]
+  pub model: String,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub api_key: String,
+  /// Overrides [`LlmProvider::default_base_url`], e.g. `https://api.groq.com/openai/v1/` or a
+  /// relay in front of Gemini.
+  #[serde(default)]
+  pub base_url: Option<String>,
+  /// `http://`, `https://` or `socks5://` proxy for model requests. Without one the system proxy
+  /// variables (`HTTPS_PROXY`, `ALL_PROXY`, …) still apply.
+  #[serde(default)]
+  pub proxy: Option<String>,
+  /// Whole-request timeout; [`DEFAULT_TIMEOUT_SECS`] when unset.
+  #[serde(default)]
+  pub timeout_secs: Option<u64>,
+  /// Sent with every request, e.g. an access token required by a relay.
+  #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+  pub headers: BTreeMap<String, String>,
+  /// Ask the model once more, listing its mistakes, when none of its place types or tags are in
+  /// the catalog (see [`crate::validate::repair`]).
+  #[serde(default)]
+  pub reask_on_invalid: bool,
+  /// Record model exchanges to [`LlmConfig::fixture_dir`], or answer from it without a model.
+  #[serde(default)]
+  pub fixture_mode: FixtureMode,
+  /// [`DEFAULT_FIXTURE_DIR`] when unset.
+  #[serde(default)]
+  pub fixture_dir: Option<String>,
+  /// Tags of each pool listed in the system prompt, picked by relevance to the prompt (see
+  /// [`crate::prefilter`]); `null` lists whole pools.
+  #[serde(default = "default_prompt_tags")]
+  pub prompt_tags: Option<usize>,
+}
+
+fn default_prompt_tags() -> Option<usize> {
+  Some(DEFAULT_PROMPT_TAGS)
+}
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+
+pub const DEFAULT_FIXTURE_DIR: &str = "fixtures/llm";
+
+impl LlmConfig {
+  pub fn new(provider: LlmProvider) -> Self {
+    Self {
+      provider,
+      model: String::new(),
+      api_key: String::new(),
+      base_url: None,
+      proxy: None,
+      timeout_secs: None,
+      headers: BTreeMap::new(),
+      reask_on_invalid: false,
+      fixture_mode: FixtureMode::Off,
+      fixture_dir: None,
+      prompt_tags: default_prompt_tags(),
+    }
+  }
+
+  pub fn model(&self) -> &str {
+    match self.model.trim() {
+      "" => self.provider.default_model(),
+      model => model,
+    }
+  }
+
+  /// Always ends with `/`, since request paths are appended to it.
+  pub fn base_url(&self) -> String {
+    let base_url = match self.base_url.as_deref().map(str::trim) {
+      Some(url) if !url.is_empty() => url,
+      _ => self.provider.default_base_url(),
+    };
+    match base_url.ends_with('/') {
+      true => base_url.to_string(),
+      false => format!("{base_url}/"),
+    }
+  }
+
+  pub fn proxy(&self) -> Option<&str> {
+    self.proxy.as_deref().map(str::trim).filter(|f| !f.is_empty())
+  }
+
+  pub fn timeout(&self) -> Duration {
+    Duration::from_secs(self.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+  }
+
+  pub fn fixture_dir(&self) -> PathBuf {
+    let dir = self.fixture_dir.as_deref().map(str::trim).filter(|f| !f.is_empty());
+    PathBuf::from(dir.unwrap_or(DEFAULT_FIXTURE_DIR))
+  }
+
+  /// The configured key, or else the one in the provider's [`LlmProvider::api_key_env`].
+  pub fn api_key(&self) -> String {
+    match self.api_key.trim() {
+      "" => self.provider.api_key_env().and_then(|f| std::env::var(f).ok()).unwrap_or_default(),
+      key => key.to_string(),
+    }
+  }
+
+  /// False when the provider needs a key and none is set, in the config or its environment
+  /// variable; such a config cannot analyze anything. Replaying fixtures needs no key.
+  pub fn has_credentials(&self) -> bool {
+    self.fixture_mode == FixtureMode::Replay
+      || self.provider.api_key_env().is_none()
+      || !self.api_key().trim().is_empty()
+  }
+
+  /// `provider/model`, the [`PromptAnalyzer::name`] of an analyzer built from this config.
+  pub fn label(&self) -> String {
+    format!("{}/{}", self.provider, self.model())
+  }
+}
+
+impl Default for LlmConfig {
+  fn default() -> Self {
+    Self::new(LlmProvider::default())
+  }
+}
@@ -0,0 +1,143 @@
+//! Remembered prompt analyses, so a repeated prompt costs no model round trip. The storage is up
+//! to the caller (a file in the desktop app); this module only keeps the entries and decides when
+//! they are stale. The offline analyzer is cheap and deterministic, so only model analyses are
+//! worth caching.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::place::fnv1a;
+use crate::{normalize, LlmConfig, PlaceCatalog, PromptAnalyses};
+
+pub const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+pub const DEFAULT_MAX_ENTRIES: usize = 500;
+
+/// How long entries live and how many are kept.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct CachePolicy {
+  #[serde(default = "default_ttl_secs")]
+  pub ttl_secs: u64,
+  #[serde(default = "default_max_entries")]
+  pub max_entries: usize,
+}
+
+fn default_ttl_secs() -> u64 {
+  DEFAULT_TTL_SECS
+}
+
+fn default_max_entries() -> usize {
+  DEFAULT_MAX_ENTRIES
+}
+
+impl Default for CachePolicy {
+  fn default() -> Self {
+    Self { ttl_secs: DEFAULT_TTL_SECS, max_entries: DEFAULT_MAX_ENTRIES }
+  }
+}
+
+/// Identifies what an analysis was made against: the catalog version and its place types and tag
+/// pools, so editing a pool without bumping the version still invalidates the cache.
+pub fn catalog_fingerprint(catalog: &PlaceCatalog) -> String {
+  let mut text = String::new();
+  for category in &catalog.categories {
+    text += category.def.place_type.as_str();
+    for tag in &category.data.tag_pool {
+      text.push('\u{1f}');
+      text += tag;
+    }
+    text.push('\u{1e}');
+  }
+  format!("{}-{:016x}", catalog.version, fnv1a(text.as_bytes()))
+}
+
+/// Cache key of a prompt analyzed with `config`; spelling variants that [`normalize`] folds share
+/// a key. Besides the model and the endpoint serving it, the key covers
+/// [`LlmConfig::prompt_tags`], since the tags the [`crate::prefilter`] lists in the system prompt
+/// shape the answer, and [`LlmConfig::reask_on_invalid`], which can replace it.
+pub fn cache_key(prompt: &str, config: &LlmConfig, fingerprint: &str) -> String {
+  let prompt_tags = config.prompt_tags.map_or("all".to_string(), |f| f.to_string());
+  let text = format!(
+    "{}\u{1f}{}\u{1f}{}\u{1f}{prompt_tags}\u{1f}{}\u{1f}{fingerprint}",
+    normalize(prompt).trim(),
+    config.label(),
+    config.base_url(),
+    config.reask_on_invalid,
+  );
+  format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CacheEntry {
+  pub analyses: PromptAnalyses,
+  /// Seconds since the Unix epoch.
+  pub stored_at: u64,
+}
+
+/// Analyses by [`cache_key`], all made against one catalog. Callers pass the time in, as seconds
+/// since the Unix epoch, so the cache stays testable.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AnalysisCache {
+  /// [`catalog_fingerprint`] of the catalog the entries were made against.
+  #[serde(default)]
+  pub catalog: String,
+  #[serde(default)]
+  pub entries: BTreeMap<String, CacheEntry>,
+}
+
+impl AnalysisCache {
+  /// Drops every entry when the catalog changed since they were stored.
+  pub fn check_catalog(&mut self, fingerprint: &str) {
+    if self.catalog != fingerprint {
+      self.entries.clear();
+      self.catalog = fingerprint.to_string();
+    }
+  }
+
+  /// The entry for `key` unless it outlived the policy's TTL; expired entries are removed.
+  pub fn get(&mut self, key: &str, policy: &CachePolicy, now: u64) -> Option<PromptAnalyses> {
+    let entry = self.entries.get(key)?;
+    if now.saturating_sub(entry.stored_at) <= policy.ttl_secs {
+      return Some(entry.analyses.clone());
+    }
+    self.entries.remove(key);
+    None
+  }
+
+  /// Stores `analyses`, then drops expired entries and the oldest ones beyond the size limit.
+  pub fn insert(&mut self, key: String, analyses: PromptAnalyses, policy: &CachePolicy, now: u64) {
+    self.entries.insert(key, CacheEntry { analyses, stored_at: now });
+    self.entries.retain(|_, entry| now.saturating_sub(entry.stored_at) <= policy.ttl_secs);
+    if self.entries.len() > policy.max_entries {
+      let mut ages =
+        self.entries.iter().map(|(key, f)| (f.stored_at, key.clone())).collect::<Vec<_>>();
+      ages.sort();
+      for (_, key) in ages.into_iter().take(self.entries.len() - policy.max_entries) {
+        self.entries.remove(&key);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::LlmProvider;
+
+  #[test]
+  fn cache_key_covers_the_model_endpoint_and_prompt_tags() {
+    let config = LlmConfig::new(LlmProvider::Gemini);
+    let key = cache_key("موزه هاي تهران", &config, "v1");
+    assert_eq!(key, cache_key(" موزه های تهران ", &config, "v1"));
+    assert_ne!(key, cache_key("موزه های تهران", &config, "v2"));
+
+    let other_model = LlmConfig { model: "gemini-1.5-pro".to_string(), ..config.clone() };
+    let all_tags = LlmConfig { prompt_tags: None, ..config.clone() };
+    let fewer_tags = LlmConfig { prompt_tags: Some(10), ..config.clone() };
+    let relay =
+      LlmConfig { base_url: Some("https://relay.example/v1beta/".to_string()), ..config.clone() };
+    let reasking = LlmConfig { reask_on_invalid: true, ..config.clone() };
+    for other in [other_model, all_tags, fewer_tags, relay, reasking] {
+      assert_ne!(key, cache_key("موزه های تهران", &other, "v1"));
+    }
+  }
+}
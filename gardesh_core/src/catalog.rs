@@ -0,0 +1,202 @@
+use derive_more::{Display, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::constraints::ConstraintKind;
+use crate::gazetteer::{Gazetteer, Landmark};
+use crate::spatial::SpatialIndex;
+use crate::{Location, NeshanDataModel, Place, PlaceId, PlaceType};
+
+/// Name of the file that describes a data pack; every category file is resolved relative to it.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Registry entry of a place category; everything the app knows about a type comes from here.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CategoryDef {
+  /// Persian label, e.g. `موزه`; also the `type` of every item in `file`.
+  pub place_type: PlaceType,
+  /// Persian plural used in the system prompt, e.g. `موزه ها`.
+  pub label_fa_plural: String,
+  pub label_en: String,
+  /// Font Awesome class of the map marker, e.g. `fa-university`.
+  pub icon: String,
+  /// Default time spent at a place of this category.
+  pub visit_minutes: u32,
+  /// Values of `type` in raw Neshan search results that belong to this category.
+  #[serde(default)]
+  pub source_kinds: Vec<String>,
+  /// Persian words that ask for this category besides its labels, e.g. `ناهار` for restaurants;
+  /// used by [`crate::offline::OfflineAnalyzer`].
+  #[serde(default)]
+  pub keywords: Vec<String>,
+  /// Prompt constraints that apply to places of this category, e.g. `diet` for restaurants;
+  /// other constraints never filter them out.
+  #[serde(default)]
+  pub constraints: Vec<ConstraintKind>,
+  /// `NeshanDataModel` file holding the category's tag pool and items.
+  pub file: String,
+}
+
+/// Area a catalog claims to cover, as a longitude/latitude box.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Region {
+  pub name: String,
+  pub south_west: Location,
+  pub north_east: Location,
+}
+
+impl Region {
+  pub fn contains(&self, location: &Location) -> bool {
+    (self.south_west.x..=self.north_east.x).contains(&location.x)
+      && (self.south_west.y..=self.north_east.y).contains(&location.y)
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CatalogManifest {
+  /// Bumped by the data team on every release; a changed version is what triggers a reload.
+  pub version: String,
+  #[serde(default)]
+  pub region: Option<Region>,
+  pub categories: Vec<CategoryDef>,
+  /// Squares and other spots a trip may start from that no category lists.
+  #[serde(default)]
+  pub landmarks: Vec<Landmark>,
+}
+
+impl CatalogManifest {
+  /// Maps a raw `type` such as `museum`, or an already Persian label, to its category.
+  pub fn place_type_for_source_kind(&self, kind: &str) -> Option<PlaceType> {
+    let kind = kind.trim();
+    self
+      .categories
+      .iter()
+      .find(|f| {
+        f.place_type.as_str() == kind || f.source_kinds.iter().any(|s| s.eq_ignore_ascii_case(kind))
+      })
+      .map(|f| f.place_type.clone())
+  }
+
+  pub fn parse(text: &str) -> Result<Self, CatalogError> {
+    serde_json::from_str(text)
+      .map_err(|source| CatalogError::Parse { file: MANIFEST_FILE.to_string(), source })
+  }
+}
+
+#[derive(Debug, Display, Error)]
+pub enum CatalogError {
+  #[display("could not read catalog file {file}: {message}")]
+  Read { file: String, message: String },
+  #[display("could not parse catalog file {file}: {source}")]
+  Parse { file: String, source: serde_json::Error },
+}
+
+#[derive(Debug, Clone)]
+pub struct Category {
+  pub def: CategoryDef,
+  pub data: NeshanDataModel,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlaceCatalog {
+  pub version: String,
+  pub region: Option<Region>,
+  pub categories: Vec<Category>,
+  /// Built over [`PlaceCatalog::places`] at load time; entries are positions in that order.
+  spatial: SpatialIndex,
+  gazetteer: Gazetteer,
+}
+
+impl PlaceCatalog {
+  /// Builds the catalog from a manifest and a reader returning the text of each category file.
+  ///
+  /// Reading is left to the caller because the wasm build fetches over HTTP while the Tauri
+  /// backend reads from the app-data directory.
+  pub fn load(
+    manifest: CatalogManifest,
+    mut read: impl FnMut(&str) -> Result<String, String>,
+  ) -> Result<Self, CatalogError> {
+    let categories = manifest
+      .categories
+      .into_iter()
+      .map(|def| {
+        let text = read(&def.file)
+          .map_err(|message| CatalogError::Read { file: def.file.clone(), message })?;
+        let mut data = serde_json::from_str::<NeshanDataModel>(&text)
+          .map_err(|source| CatalogError::Parse { file: def.file.clone(), source })?;
+        data.ensure_ids();
+        Ok(Category { def, data })
+      })
+      .collect::<Result<Vec<_>, CatalogError>>()?;
+    let spatial =
+      SpatialIndex::build(categories.iter().flat_map(|f| &f.data.items).map(|f| &f.location));
+    let gazetteer =
+      Gazetteer::build(categories.iter().flat_map(|f| &f.data.items), &manifest.landmarks);
+    Ok(Self { version: manifest.version, region: manifest.region, categories, spatial, gazetteer })
+  }
+
+  fn place_at(&self, mut index: usize) -> &Place {
+    for category in &self.categories {
+      match category.data.items.get(index) {
+        Some(place) => return place,
+        None => index -= category.data.items.len(),
+      }
+    }
+    panic!("spatial index entry out of range")
+  }
+
+  /// The `n` places closest to `origin` accepted by `filter`, with their distance in meters.
+  pub fn nearest(
+    &self,
+    origin: &Location,
+    n: usize,
+    filter: impl Fn(&Place) -> bool,
+  ) -> Vec<(&Place, f64)> {
+    let found = self.spatial.nearest(origin, n, |i| filter(self.place_at(i)));
+    found.into_iter().map(|(i, distance)| (self.place_at(i), distance)).collect()
+  }
+
+  /// Places within `radius` meters of `origin` accepted by `filter`, nearest first.
+  pub fn within_radius(
+    &self,
+    origin: &Location,
+    radius: f64,
+    filter: impl Fn(&Place) -> bool,
+  ) -> Vec<(&Place, f64)> {
+    let found = self.spatial.within_radius(origin, radius, |i| filter(self.place_at(i)));
+    found.into_iter().map(|(i, distance)| (self.place_at(i), distance)).collect()
+  }
+
+  /// Places inside the box spanned by two opposite corners.
+  pub fn in_bounds(&self, corner1: &Location, corner2: &Location) -> Vec<&Place> {
+    self.spatial.in_bounds(corner1, corner2).into_iter().map(|i| self.place_at(i)).collect()
+  }
+
+  /// Place titles, landmarks and neighbourhoods a trip can start from.
+  pub fn gazetteer(&self) -> &Gazetteer {
+    &self.gazetteer
+  }
+
+  pub fn category(&self, place_type: &PlaceType) -> Option<&Category> {
+    self.categories.iter().find(|f| f.def.place_type == *place_type)
+  }
+
+  pub fn place_types(&self) -> impl Iterator<Item = &PlaceType> {
+    self.categories.iter().map(|f| &f.def.place_type)
+  }
+
+  pub fn tag_pool(&self, place_type: &PlaceType) -> &[String] {
+    self.category(place_type).map(|f| f.data.tag_pool.as_slice()).unwrap_or_default()
+  }
+
+  pub fn places(&self) -> impl Iterator<Item = &Place> {
+    self.categories.iter().flat_map(|f| &f.data.items)
+  }
+
+  pub fn place(&self, id: &PlaceId) -> Option<&Place> {
+    self.places().find(|place| place.id == *id)
+  }
+
+  pub fn places_of(&self, place_type: &PlaceType) -> impl Iterator<Item = &Place> {
+    self.category(place_type).into_iter().flat_map(|f| &f.data.items)
+  }
+}
@@ -0,0 +1,281 @@
+//! What a prompt asks of the trip beyond place types and tags: a time budget, a price level,
+//! diets and wheelchair access. Places are checked against the attributes the catalog records
+//! for them; a constraint no place of a category is known to meet is dropped for that category
+//! and reported instead of sinking the plan.
+//!
+//! The shipped data pack records no attributes yet: Neshan search results carry none, so they
+//! have to be curated into the tagged catalog files, which `ingest` carries them over from. Until
+//! then every price, diet and wheelchair constraint is reported unmet and only the time budget
+//! changes the plan.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+
+use crate::{distance_haversine, Location, Place, PlaceCatalog, PlaceType};
+
+/// Average speed of getting between stops in city traffic, in meters per minute (15 km/h).
+const TRAVEL_METERS_PER_MINUTE: f64 = 250.0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceLevel {
+  Cheap,
+  Moderate,
+  Expensive,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Diet {
+  Vegetarian,
+  Vegan,
+  GlutenFree,
+}
+
+/// A constraint checked against [`PlaceAttributes`]; categories list the ones that apply to
+/// them in [`crate::catalog::CategoryDef::constraints`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstraintKind {
+  Price,
+  Diet,
+  Wheelchair,
+}
+
+/// Constraints of a prompt; the default asks for nothing.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+  /// Minutes for the whole trip: visits, and travel from the start and between stops.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub time_budget_minutes: Option<u32>,
+  /// Dearest price level accepted.
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub max_price: Option<PriceLevel>,
+  /// Diets every place serving food must cater for.
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub diets: Vec<Diet>,
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub wheelchair: bool,
+}
+
+impl Constraints {
+  pub fn is_empty(&self) -> bool {
+    *self == Self::default()
+  }
+
+  /// Constraints asked for that are checked per place.
+  pub fn kinds(&self) -> Vec<ConstraintKind> {
+    let mut kinds = Vec::new();
+    if self.max_price.is_some() {
+      kinds.push(ConstraintKind::Price);
+    }
+    if !self.diets.is_empty() {
+      kinds.push(ConstraintKind::Diet);
+    }
+    if self.wheelchair {
+      kinds.push(ConstraintKind::Wheelchair);
+    }
+    kinds
+  }
+}
+
+/// What the catalog knows about a place beyond its tags; unset fields are unknown, and an
+/// unknown attribute never meets a constraint.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq, Hash)]
+pub struct PlaceAttributes {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub price_level: Option<PriceLevel>,
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub diets: Vec<Diet>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub wheelchair: Option<bool>,
+}
+
+impl PlaceAttributes {
+  pub fn is_empty(&self) -> bool {
+    *self == Self::default()
+  }
+
+  pub fn meets(&self, kind: ConstraintKind, constraints: &Constraints) -> bool {
+    match kind {
+      ConstraintKind::Price => match (self.price_level, constraints.max_price) {
+        (_, None) => true,
+        (Some(level), Some(max)) => level <= max,
+        (None, Some(_)) => false,
+      },
+      ConstraintKind::Diet => constraints.diets.iter().all(|f| self.diets.contains(f)),
+      ConstraintKind::Wheelchair => !constraints.wheelchair || self.wheelchair == Some(true),
+    }
+  }
+}
+
+/// A constraint the plan does not honor, to be shown to the user.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum UnmetConstraint {
+  /// No candidate of `place_type` is known to meet `constraint`, so its stops ignore it.
+  Attribute { constraint: ConstraintKind, place_type: PlaceType },
+  /// The plan took `planned_minutes`; `dropped` stops were cut from its end to fit the budget,
+  /// or as many as could be while keeping one.
+  TimeBudget { budget_minutes: u32, planned_minutes: u32, dropped: usize },
+  /// The prompt starts the trip from `name`, which the gazetteer does not know; the route is
+  /// planned without a start.
+  UnknownStart { name: String },
+}
+
+impl UnmetConstraint {
+  /// One-line Persian explanation shown next to the plan.
+  pub fn message(&self) -> String {
+    match self {
+      UnmetConstraint::Attribute { constraint, place_type } => {
+        let wanted = match constraint {
+          ConstraintKind::Price => "قیمت مورد نظر",
+          ConstraintKind::Diet => "رژیم غذایی مورد نظر",
+          ConstraintKind::Wheelchair => "دسترسی با ویلچر",
+        };
+        format!("برای {place_type} اطلاعی از {wanted} نداریم؛ این شرط در نظر گرفته نشد.")
+      }
+      UnmetConstraint::TimeBudget { budget_minutes, planned_minutes, dropped: 0 } => {
+        format!("برنامه {planned_minutes} دقیقه طول میکشد و از وقت شما ({budget_minutes} دقیقه) بیشتر است.")
+      }
+      UnmetConstraint::TimeBudget { budget_minutes, planned_minutes, dropped } => {
+        format!(
+          "برنامه {planned_minutes} دقیقه طول میکشید و وقت شما {budget_minutes} دقیقه است؛ \
+           {dropped} مکان از آخر برنامه حذف شد."
+        )
+      }
+      UnmetConstraint::UnknownStart { name } => {
+        format!("«{name}» را پیدا نکردیم؛ برنامه بدون نقطه شروع چیده شد.")
+      }
+    }
+  }
+}
+
+impl Display for UnmetConstraint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      UnmetConstraint::Attribute { constraint, place_type } => {
+        write!(f, "no {place_type} known to meet the {constraint:?} constraint")
+      }
+      UnmetConstraint::TimeBudget { budget_minutes, planned_minutes, dropped } => write!(
+        f,
+        "plan took {planned_minutes} of {budget_minutes} minutes; dropped {dropped} stops"
+      ),
+      UnmetConstraint::UnknownStart { name } => write!(f, "unknown start `{name}`"),
+    }
+  }
+}
+
+/// Minutes a trip through `places` in order takes: each category's visit time plus travel, from
+/// `start` when given.
+pub fn trip_minutes(catalog: &PlaceCatalog, places: &[&Place], start: Option<&Location>) -> u32 {
+  let visits = places
+    .iter()
+    .map(|f| catalog.category(&f.r#type).map_or(0, |category| category.def.visit_minutes))
+    .sum::<u32>();
+  let legs = places.windows(2).map(|f| distance_haversine(&f[0].location, &f[1].location));
+  let first = start.zip(places.first()).map(|(start, f)| distance_haversine(start, &f.location));
+  let meters = legs.chain(first).sum::<f64>();
+  visits + (meters / TRAVEL_METERS_PER_MINUTE).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::offline::analyze_offline;
+  use crate::plan_checked;
+  use crate::testing::{catalog, place_type, HISTORY, RESTAURANT};
+
+  fn attributes(
+    price_level: Option<PriceLevel>,
+    diets: &[Diet],
+    wheelchair: Option<bool>,
+  ) -> PlaceAttributes {
+    PlaceAttributes { price_level, diets: diets.to_vec(), wheelchair }
+  }
+
+  #[test]
+  fn price_meets_the_dearest_level_accepted() {
+    let moderate = Constraints { max_price: Some(PriceLevel::Moderate), ..Default::default() };
+    let meets = |level| attributes(level, &[], None).meets(ConstraintKind::Price, &moderate);
+    assert!(meets(Some(PriceLevel::Cheap)));
+    assert!(meets(Some(PriceLevel::Moderate)));
+    assert!(!meets(Some(PriceLevel::Expensive)));
+    // An unknown price never meets a constraint, but no constraint is always met.
+    assert!(!meets(None));
+    assert!(attributes(None, &[], None).meets(ConstraintKind::Price, &Constraints::default()));
+  }
+
+  #[test]
+  fn diets_must_all_be_catered_for() {
+    let both =
+      Constraints { diets: vec![Diet::Vegetarian, Diet::GlutenFree], ..Default::default() };
+    let meets = |diets: &[Diet]| attributes(None, diets, None).meets(ConstraintKind::Diet, &both);
+    assert!(meets(&[Diet::GlutenFree, Diet::Vegan, Diet::Vegetarian]));
+    assert!(!meets(&[Diet::Vegetarian]));
+    assert!(!meets(&[]));
+  }
+
+  #[test]
+  fn wheelchair_access_must_be_known() {
+    let wheelchair = Constraints { wheelchair: true, ..Default::default() };
+    let meets = |known| attributes(None, &[], known).meets(ConstraintKind::Wheelchair, &wheelchair);
+    assert!(meets(Some(true)));
+    assert!(!meets(Some(false)));
+    assert!(!meets(None));
+  }
+
+  #[test]
+  fn kinds_lists_the_constraints_asked_for() {
+    assert!(Constraints::default().kinds().is_empty());
+    let constraints = Constraints {
+      time_budget_minutes: Some(120),
+      diets: vec![Diet::Vegan],
+      wheelchair: true,
+      ..Default::default()
+    };
+    assert_eq!(constraints.kinds(), [ConstraintKind::Diet, ConstraintKind::Wheelchair]);
+  }
+
+  #[test]
+  fn trip_minutes_adds_visits_and_travel() {
+    let catalog = catalog();
+    let place = |title: &str| catalog.places().find(|f| f.title == title).unwrap();
+    let (palace, restaurant) = (place("کاخ گلستان"), place("رستوران شهرزاد"));
+    assert_eq!(trip_minutes(&catalog, &[], None), 0);
+    assert_eq!(trip_minutes(&catalog, &[palace], None), 60);
+    let travel =
+      distance_haversine(&palace.location, &restaurant.location) / TRAVEL_METERS_PER_MINUTE;
+    assert_eq!(
+      trip_minutes(&catalog, &[palace, restaurant], None),
+      60 + 75 + travel.round() as u32
+    );
+    let start = Location { x: 51.4200, y: 35.7025 };
+    assert!(trip_minutes(&catalog, &[palace], Some(&start)) > 60);
+  }
+
+  #[test]
+  fn prompt_constraints_pick_matching_places() {
+    let catalog = catalog();
+    let analyses = analyze_offline(&catalog, "یه رستوران ارزون و گیاهخوار و یه کاخ با ویلچر");
+    let constraints = &analyses.constraints;
+    assert_eq!(constraints.max_price, Some(PriceLevel::Cheap));
+    assert_eq!(constraints.diets, [Diet::Vegetarian]);
+    assert!(constraints.wheelchair);
+    let plan = plan_checked(&catalog, &analyses).unwrap();
+    let picked = |label: &str| {
+      let stop = plan.stops.iter().find(|f| f.info.place_type == place_type(label)).unwrap();
+      stop.candidates.iter().map(|f| f.title.as_str()).collect::<Vec<_>>()
+    };
+    assert_eq!(picked(RESTAURANT), ["رستوران شهرزاد"]);
+    // No historical place is known to be cheap, so price is ignored for them and reported.
+    assert_eq!(picked(HISTORY), ["کاخ گلستان"]);
+    assert_eq!(
+      plan.unmet,
+      [UnmetConstraint::Attribute {
+        constraint: ConstraintKind::Price,
+        place_type: place_type(HISTORY)
+      }]
+    );
+  }
+}
@@ -0,0 +1,407 @@
+//! Follow-up prompts within a session. Once a session has a plan, a new prompt such as "swap the
+//! restaurant for a traditional one" is read as a list of [`PlanEdit`]s, and only the stops it
+//! touches are planned again.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::plan::{candidates, route_cost, MAX_ROUTE_CANDIDATES, MAX_STOPS};
+use crate::{
+  Place, PlaceCatalog, PlaceId, PlaceInfo, PlaceScoring, PlaceType, PlanError, PlanStop,
+};
+
+/// Most recent messages of a session sent along with a follow-up.
+pub const MAX_HISTORY: usize = 12;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatRole {
+  User,
+  Assistant,
+}
+
+/// One turn of a session: a prompt, or the plan it was answered with (see [`describe_plan`]).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct ChatMessage {
+  pub role: ChatRole,
+  pub content: String,
+}
+
+impl ChatMessage {
+  pub fn user(content: impl Into<String>) -> Self {
+    Self { role: ChatRole::User, content: content.into() }
+  }
+
+  pub fn assistant(content: impl Into<String>) -> Self {
+    Self { role: ChatRole::Assistant, content: content.into() }
+  }
+}
+
+/// One change a follow-up asks for. `stop` is the 1-based number of a stop as listed by
+/// [`describe_plan`]; when it is unset, the last stop of the given place type is meant.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum PlanEdit {
+  /// `info.count` new stops, each placed where it lengthens the loop least among the places its
+  /// `info.order` allows.
+  Add {
+    #[serde(flatten)]
+    info: PlaceInfo,
+  },
+  /// A different place for an existing stop, matching `info` instead of the stop's own info.
+  Replace {
+    #[serde(default)]
+    stop: Option<usize>,
+    #[serde(flatten)]
+    info: PlaceInfo,
+  },
+  Remove {
+    #[serde(default)]
+    stop: Option<usize>,
+    place_type: PlaceType,
+  },
+}
+
+/// The model's answer to a follow-up, as described by [`crate::prompt::edits_schema`].
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PlanEdits {
+  #[serde(default)]
+  pub edits: Vec<PlanEdit>,
+}
+
+/// Numbered stops of a plan, one per line; the form a plan takes in the chat history.
+pub fn describe_plan(stops: &[PlanStop]) -> String {
+  stops
+    .iter()
+    .enumerate()
+    .map(|(i, stop)| {
+      let title = stop.candidates.first().map(|f| f.title.as_str()).unwrap_or_default();
+      match stop.info.tags.is_empty() {
+        true => format!("{}. {}: {title}", i + 1, stop.info.place_type),
+        false => {
+          format!("{}. {}: {title} ({})", i + 1, stop.info.place_type, stop.info.tags.join("، "))
+        }
+      }
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// A stop of a plan under edit; `picked` is unset until a place is chosen for it.
+struct Draft {
+  info: PlaceInfo,
+  picked: Option<Place>,
+  alternatives: Vec<Place>,
+  /// A replaced stop as it was, restored if no other place matches.
+  replaced: Option<PlanStop>,
+  removed: bool,
+}
+
+/// Index of the stop an edit refers to: its number when valid, else the last stop of its type.
+fn resolve(drafts: &[Draft], stop: Option<usize>, place_type: &PlaceType) -> Option<usize> {
+  let live = |i: &usize| !drafts[*i].removed;
+  stop
+    .and_then(|number| number.checked_sub(1))
+    .filter(|i| *i < drafts.len())
+    .filter(live)
+    .or_else(|| (0..drafts.len()).filter(live).rfind(|i| drafts[*i].info.place_type == *place_type))
+}
+
+/// Places in the plan, including ones being replaced, so no edit picks them again.
+fn taken(drafts: &[Draft]) -> HashSet<PlaceId> {
+  drafts
+    .iter()
+    .flat_map(|f| f.picked.iter().chain(f.replaced.as_ref().and_then(|f| f.candidates.first())))
+    .map(|f| f.id.clone())
+    .collect()
+}
+
+fn scoring(draft: &Draft, place: &Place) -> PlaceScoring {
+  PlaceScoring { score: draft.info.match_score(place), place: place.clone() }
+}
+
+/// Applies `edits` to the current plan. Stops the edits do not touch keep their places; replaced
+/// stops get the candidate that keeps the loop shortest, and added stops are inserted where they
+/// lengthen it least. No place is used twice.
+///
+/// Fails with [`PlanError::NothingRequested`] when there is nothing to apply, and with
+/// [`PlanError::NoMatch`] when no edit could be carried out.
+pub fn apply_edits(
+  catalog: &PlaceCatalog,
+  stops: &[PlanStop],
+  edits: &[PlanEdit],
+) -> Result<Vec<PlanStop>, PlanError> {
+  if edits.is_empty() {
+    return Err(PlanError::NothingRequested);
+  }
+  let mut drafts = stops
+    .iter()
+    .map(|stop| Draft {
+      info: stop.info.clone(),
+      picked: stop.candidates.first().cloned(),
+      alternatives: stop.candidates.iter().skip(1).cloned().collect(),
+      replaced: None,
+      removed: false,
+    })
+    .collect::<Vec<_>>();
+  let mut added = Vec::new();
+  let mut changed = false;
+  for edit in edits {
+    match edit {
+      PlanEdit::Remove { stop, place_type } => {
+        if let Some(i) = resolve(&drafts, *stop, place_type) {
+          drafts[i].removed = true;
+          changed = true;
+        }
+      }
+      PlanEdit::Replace { stop, info } => {
+        if let Some(i) = resolve(&drafts, *stop, &info.place_type) {
+          let draft = &mut drafts[i];
+          if draft.replaced.is_none() {
+            draft.replaced = Some(stops[i].clone());
+          }
+          draft.info = info.clone();
+          draft.picked = None;
+          draft.alternatives.clear();
+        }
+      }
+      PlanEdit::Add { info } => {
+        let count = info.count.map_or(1, |f| f.max(1) as usize);
+        added.extend(std::iter::repeat_n(PlaceInfo { count: None, ..info.clone() }, count));
+      }
+    }
+  }
+  drafts.retain(|f| !f.removed);
+
+  // Replaced stops keep their position; the loop is measured through the stops already placed.
+  for i in 0..drafts.len() {
+    if drafts[i].picked.is_some() {
+      continue;
+    }
+    let taken = taken(&drafts);
+    let options = candidates(catalog, &drafts[i].info)
+      .into_iter()
+      .filter(|f| !taken.contains(&f.place.id))
+      .collect::<Vec<_>>();
+    let fixed = drafts
+      .iter()
+      .filter_map(|draft| draft.picked.as_ref().map(|place| scoring(draft, place)))
+      .collect::<Vec<_>>();
+    let before = drafts[..i].iter().filter(|f| f.picked.is_some()).count();
+    let best = options.iter().take(MAX_ROUTE_CANDIDATES).min_by(|a, b| {
+      let cost = |option: &PlaceScoring| {
+        let mut route = fixed.iter().collect::<Vec<_>>();
+        route.insert(before, option);
+        route_cost(&route, None)
+      };
+      cost(a).total_cmp(&cost(b))
+    });
+    let draft = &mut drafts[i];
+    match best {
+      Some(best) => {
+        draft.picked = Some(best.place.clone());
+        draft.alternatives =
+          options.iter().filter(|f| f.place != best.place).map(|f| f.place.clone()).collect();
+        draft.replaced = None;
+        changed = true;
+      }
+      None => {
+        if let Some(stop) = draft.replaced.take() {
+          draft.info = stop.info;
+          draft.picked = stop.candidates.first().cloned();
+          draft.alternatives = stop.candidates.into_iter().skip(1).collect();
+        }
+      }
+    }
+  }
+  drafts.retain(|f| f.picked.is_some());
+
+  for info in added {
+    if drafts.len() >= MAX_STOPS {
+      break;
+    }
+    let draft = Draft { info, picked: None, alternatives: vec![], replaced: None, removed: false };
+    let taken = taken(&drafts);
+    let options = candidates(catalog, &draft.info)
+      .into_iter()
+      .filter(|f| !taken.contains(&f.place.id))
+      .collect::<Vec<_>>();
+    let fixed = drafts.iter().map(|f| scoring(f, f.picked.as_ref().unwrap())).collect::<Vec<_>>();
+    let allowed = (0..=drafts.len())
+      .filter(|&at| {
+        let mut sequence = drafts.iter().map(|f| &f.info.place_type).collect::<Vec<_>>();
+        sequence.insert(at, &draft.info.place_type);
+        draft.info.order.holds(at..at + 1, &sequence)
+      })
+      .collect::<Vec<_>>();
+    // Conflicting orders, like before one stop but after a later one, leave every place open.
+    let slots = match allowed.is_empty() {
+      true => (0..=drafts.len()).collect(),
+      false => allowed,
+    };
+    let best = options
+      .iter()
+      .take(MAX_ROUTE_CANDIDATES)
+      .flat_map(|option| slots.iter().map(move |&at| (option, at)))
+      .map(|(option, at)| {
+        let mut route = fixed.iter().collect::<Vec<_>>();
+        route.insert(at, option);
+        (route_cost(&route, None), option, at)
+      })
+      .min_by(|a, b| a.0.total_cmp(&b.0));
+    if let Some((_, best, at)) = best {
+      let alternatives =
+        options.iter().filter(|f| f.place != best.place).map(|f| f.place.clone()).collect();
+      drafts.insert(at, Draft { picked: Some(best.place.clone()), alternatives, ..draft });
+      changed = true;
+    }
+  }
+  if !changed {
+    return Err(PlanError::NoMatch);
+  }
+
+  let picked =
+    drafts.iter().filter_map(|f| f.picked.as_ref()).map(|f| &f.id).collect::<HashSet<_>>();
+  Ok(
+    drafts
+      .iter()
+      .map(|draft| {
+        let chosen = draft.picked.clone().unwrap();
+        let alternatives = draft.alternatives.iter().filter(|f| !picked.contains(&f.id)).cloned();
+        PlanStop {
+          info: draft.info.clone(),
+          candidates: std::iter::once(chosen).chain(alternatives).collect(),
+        }
+      })
+      .collect(),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{catalog, place_type, HISTORY, MUSEUM, RESTAURANT};
+  use crate::StopOrder;
+
+  fn info(label: &str, count: Option<u32>) -> PlaceInfo {
+    PlaceInfo { place_type: place_type(label), tags: vec![], count, order: StopOrder::Flexible }
+  }
+
+  /// A stop at the place titled `title`, with no alternatives.
+  fn stop(catalog: &PlaceCatalog, title: &str) -> PlanStop {
+    let place = catalog.places().find(|f| f.title == title).unwrap().clone();
+    PlanStop { info: info(place.r#type.as_str(), None), candidates: vec![place] }
+  }
+
+  fn titles(stops: &[PlanStop]) -> Vec<&str> {
+    stops.iter().map(|f| f.candidates[0].title.as_str()).collect()
+  }
+
+  fn plan(catalog: &PlaceCatalog) -> Vec<PlanStop> {
+    ["کاخ گلستان", "موزه ملی ایران", "رستوران شهرزاد"].map(|f| stop(catalog, f)).to_vec()
+  }
+
+  #[test]
+  fn remove_takes_the_numbered_or_last_stop_of_its_type() {
+    let catalog = catalog();
+    let mut stops = plan(&catalog);
+    stops.push(stop(&catalog, "موزه فرش"));
+    let museum = place_type(MUSEUM);
+    let edits = [PlanEdit::Remove { stop: None, place_type: museum.clone() }];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(titles(&edited), ["کاخ گلستان", "موزه ملی ایران", "رستوران شهرزاد"]);
+
+    let edits = [PlanEdit::Remove { stop: Some(1), place_type: museum.clone() }];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(titles(&edited), ["موزه ملی ایران", "رستوران شهرزاد", "موزه فرش"]);
+
+    // A second removal of the same type moves on to the next stop of it.
+    let edits = vec![PlanEdit::Remove { stop: None, place_type: museum }; 2];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(titles(&edited), ["کاخ گلستان", "رستوران شهرزاد"]);
+  }
+
+  #[test]
+  fn replace_keeps_the_position_and_avoids_planned_places() {
+    let catalog = catalog();
+    let stops = plan(&catalog);
+    let edits = [PlanEdit::Replace { stop: Some(2), info: info(MUSEUM, None) }];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(edited.len(), 3);
+    assert_eq!(titles(&edited)[0], "کاخ گلستان");
+    assert_eq!(titles(&edited)[2], "رستوران شهرزاد");
+    assert_eq!(edited[1].candidates[0].r#type, place_type(MUSEUM));
+    assert_ne!(titles(&edited)[1], "موزه ملی ایران");
+    assert!(edited[1].candidates.iter().all(|f| f.title != "موزه ملی ایران"));
+
+    // A stop may become another type.
+    let edits = [PlanEdit::Replace { stop: Some(2), info: info(RESTAURANT, None) }];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(edited[1].info.place_type, place_type(RESTAURANT));
+    assert_ne!(titles(&edited)[1], "رستوران شهرزاد");
+  }
+
+  #[test]
+  fn replace_without_a_match_keeps_the_stop() {
+    let catalog = catalog();
+    let stops = plan(&catalog);
+    let mut wanted = info(MUSEUM, None);
+    wanted.tags = vec!["ورزش".to_string()];
+    let edits = [PlanEdit::Replace { stop: Some(2), info: wanted }];
+    assert_eq!(apply_edits(&catalog, &stops, &edits).unwrap_err(), PlanError::NoMatch);
+
+    let edits =
+      [edits[0].clone(), PlanEdit::Remove { stop: None, place_type: place_type(HISTORY) }];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(titles(&edited), ["موزه ملی ایران", "رستوران شهرزاد"]);
+    assert_eq!(edited[0].info, info(MUSEUM, None));
+  }
+
+  #[test]
+  fn add_inserts_count_distinct_places() {
+    let catalog = catalog();
+    let stops = plan(&catalog);
+    let edits = [PlanEdit::Add { info: info(MUSEUM, Some(2)) }];
+    let edited = apply_edits(&catalog, &stops, &edits).unwrap();
+    assert_eq!(edited.len(), 5);
+    let museums =
+      edited.iter().filter(|f| f.info.place_type == place_type(MUSEUM)).collect::<Vec<_>>();
+    assert_eq!(museums.len(), 3);
+    assert_eq!(museums.iter().map(|f| &f.candidates[0].id).collect::<HashSet<_>>().len(), 3);
+    assert!(edited.iter().all(|f| f.info.count.is_none()));
+
+    // Only three museums exist; the third added one finds no place.
+    let edits = [PlanEdit::Add { info: info(MUSEUM, Some(3)) }];
+    assert_eq!(apply_edits(&catalog, &stops, &edits).unwrap().len(), 5);
+  }
+
+  #[test]
+  fn add_honors_the_order_and_the_stop_limit() {
+    let catalog = catalog();
+    let stops = plan(&catalog);
+    let mut first = info(RESTAURANT, None);
+    first.order = StopOrder::Position { position: 1 };
+    let edited = apply_edits(&catalog, &stops, &[PlanEdit::Add { info: first }]).unwrap();
+    assert_eq!(edited[0].info.place_type, place_type(RESTAURANT));
+    assert_eq!(&titles(&edited)[1..], titles(&stops));
+
+    // Two restaurants and two historical places are left to add.
+    let edits = [
+      PlanEdit::Add { info: info(RESTAURANT, Some(20)) },
+      PlanEdit::Add { info: info(HISTORY, Some(20)) },
+    ];
+    assert_eq!(apply_edits(&catalog, &stops, &edits).unwrap().len(), 7);
+    let many = std::iter::repeat_n(stop(&catalog, "برج آزادی"), MAX_STOPS).collect::<Vec<_>>();
+    let edits = [PlanEdit::Add { info: info(MUSEUM, None) }];
+    assert_eq!(apply_edits(&catalog, &many, &edits).unwrap_err(), PlanError::NoMatch);
+  }
+
+  #[test]
+  fn nothing_to_apply() {
+    let catalog = catalog();
+    let stops = plan(&catalog);
+    assert_eq!(apply_edits(&catalog, &stops, &[]).unwrap_err(), PlanError::NothingRequested);
+    let edits = [PlanEdit::Remove { stop: Some(9), place_type: place_type("سینما") }];
+    assert_eq!(apply_edits(&catalog, &stops, &edits).unwrap_err(), PlanError::NoMatch);
+  }
+}
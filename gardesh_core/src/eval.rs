@@ -0,0 +1,155 @@
+//! Scoring analyzers against a golden set of prompts, so models are compared on numbers rather
+//! than by reading their answers side by side. Running the analyzers and timing them is left to
+//! the caller (see the `eval` tool).
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+
+use crate::prefilter::PromptSavings;
+use crate::{normalize, PlaceInfo, PlaceType, PromptAnalyses};
+
+/// A prompt and the analysis it should get. The tags of each expected info are the acceptable
+/// ones: any of them counts as a correct tag, and each one missing from the answer lowers recall.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GoldenCase {
+  pub prompt: String,
+  pub place_infos: Vec<PlaceInfo>,
+  #[serde(default)]
+  pub total_count: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct GoldenSet {
+  pub cases: Vec<GoldenCase>,
+}
+
+/// How one answer compares to its [`GoldenCase`].
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaseScore {
+  /// Tags the answer gave, over all its infos.
+  pub tags_given: usize,
+  /// Acceptable tags over all expected infos.
+  pub tags_expected: usize,
+  /// Given tags acceptable for an expected info of the same place type.
+  pub tags_matched: usize,
+  /// The answer names exactly the expected place types.
+  pub types_correct: bool,
+  pub total_count_correct: bool,
+}
+
+fn tag_set<'a>(infos: impl Iterator<Item = &'a PlaceInfo>) -> HashSet<(&'a PlaceType, String)> {
+  infos.flat_map(|info| info.tags.iter().map(|tag| (&info.place_type, normalize(tag)))).collect()
+}
+
+fn place_types(infos: &[PlaceInfo]) -> HashSet<&PlaceType> {
+  infos.iter().map(|f| &f.place_type).collect()
+}
+
+impl CaseScore {
+  pub fn new(case: &GoldenCase, answer: &PromptAnalyses) -> Self {
+    let expected = tag_set(case.place_infos.iter());
+    let given = tag_set(answer.place_infos.iter());
+    Self {
+      tags_given: given.len(),
+      tags_expected: expected.len(),
+      tags_matched: given.intersection(&expected).count(),
+      types_correct: place_types(&case.place_infos) == place_types(&answer.place_infos),
+      total_count_correct: case.total_count == answer.total_count,
+    }
+  }
+}
+
+/// One golden prompt put to an analyzer. A failed request scores as an empty answer.
+#[derive(Debug, Serialize, Clone)]
+pub struct CaseRun {
+  pub prompt: String,
+  pub latency_ms: u64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub answer: Option<PromptAnalyses>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+  pub score: CaseScore,
+}
+
+impl CaseRun {
+  pub fn new(case: &GoldenCase, latency_ms: u64, answer: Result<PromptAnalyses, String>) -> Self {
+    let score = CaseScore::new(case, answer.as_ref().unwrap_or(&PromptAnalyses::default()));
+    let (answer, error) = match answer {
+      Ok(answer) => (Some(answer), None),
+      Err(err) => (None, Some(err)),
+    };
+    Self { prompt: case.prompt.clone(), latency_ms, answer, error, score }
+  }
+}
+
+/// Totals of one analyzer over the golden set. Ratios are in `0..=1`; precision and recall are
+/// over all tags of the set rather than averaged per case.
+#[derive(Debug, Serialize, Clone)]
+pub struct ModelScore {
+  pub analyzer: String,
+  pub cases: usize,
+  pub errors: usize,
+  pub tag_precision: f64,
+  pub tag_recall: f64,
+  pub type_accuracy: f64,
+  pub total_count_accuracy: f64,
+  pub mean_latency_ms: f64,
+  pub max_latency_ms: u64,
+  /// Mean system prompt size over the set, for analyzers that send one.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub prompt_size: Option<PromptSavings>,
+  pub runs: Vec<CaseRun>,
+}
+
+/// `part / whole`, or 1 when there was nothing to get right.
+fn ratio(part: usize, whole: usize) -> f64 {
+  match whole {
+    0 => 1.0,
+    _ => part as f64 / whole as f64,
+  }
+}
+
+impl ModelScore {
+  pub fn new(analyzer: String, runs: Vec<CaseRun>) -> Self {
+    let sum = |f: fn(&CaseScore) -> usize| runs.iter().map(|run| f(&run.score)).sum::<usize>();
+    let cases = runs.len();
+    let latency = runs.iter().map(|f| f.latency_ms).sum::<u64>();
+    Self {
+      analyzer,
+      cases,
+      errors: runs.iter().filter(|f| f.error.is_some()).count(),
+      tag_precision: ratio(sum(|f| f.tags_matched), sum(|f| f.tags_given)),
+      tag_recall: ratio(sum(|f| f.tags_matched), sum(|f| f.tags_expected)),
+      type_accuracy: ratio(sum(|f| usize::from(f.types_correct)), cases),
+      total_count_accuracy: ratio(sum(|f| usize::from(f.total_count_correct)), cases),
+      mean_latency_ms: if cases == 0 { 0.0 } else { latency as f64 / cases as f64 },
+      max_latency_ms: runs.iter().map(|f| f.latency_ms).max().unwrap_or(0),
+      prompt_size: None,
+      runs,
+    }
+  }
+}
+
+impl Display for ModelScore {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{}: tag precision {:.2}, tag recall {:.2}, type accuracy {:.2}, total_count accuracy \
+       {:.2}, latency {:.0} ms mean / {} ms max, {} of {} cases failed",
+      self.analyzer,
+      self.tag_precision,
+      self.tag_recall,
+      self.type_accuracy,
+      self.total_count_accuracy,
+      self.mean_latency_ms,
+      self.max_latency_ms,
+      self.errors,
+      self.cases
+    )?;
+    if let Some(prompt_size) = &self.prompt_size {
+      write!(f, "\n  {prompt_size}")?;
+    }
+    Ok(())
+  }
+}
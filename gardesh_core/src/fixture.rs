@@ -0,0 +1,74 @@
+//! Recorded model exchanges, so analysis and planning can run without a key or network. In record
+//! mode every request and its raw answer is written to a fixture directory; in replay mode the
+//! answer is read back from there instead of asking the model.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::conversation::{ChatMessage, ChatRole};
+use crate::place::fnv1a;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum FixtureMode {
+  /// Every request goes to the model.
+  #[default]
+  Off,
+  /// Requests go to the model and each answer is saved.
+  Record,
+  /// Answers come from saved fixtures only; a request without one fails.
+  Replay,
+}
+
+/// One request and the model's raw answer, stored as `<request_hash>.json`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Exchange {
+  pub system: String,
+  pub messages: Vec<ChatMessage>,
+  pub response: String,
+}
+
+/// Identifies a request by its system prompt and messages. The model is left out, so fixtures
+/// recorded with one model replay under any other.
+pub fn request_hash(system: &str, messages: &[ChatMessage]) -> String {
+  let mut text = system.to_string();
+  for message in messages {
+    let role = match message.role {
+      ChatRole::User => "user",
+      ChatRole::Assistant => "assistant",
+    };
+    text += &format!("\u{1e}{role}\u{1f}{}", message.content);
+  }
+  format!("{:016x}", fnv1a(text.as_bytes()))
+}
+
+pub fn fixture_path(dir: &Path, hash: &str) -> PathBuf {
+  dir.join(format!("{hash}.json"))
+}
+
+/// The recorded answer to a request, or `None` when it was never recorded.
+pub fn replay(
+  dir: &Path,
+  system: &str,
+  messages: &[ChatMessage],
+) -> Result<Option<String>, String> {
+  let path = fixture_path(dir, &request_hash(system, messages));
+  let text = match fs::read_to_string(&path) {
+    Ok(text) => text,
+    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+    Err(err) => return Err(format!("{}: {err}", path.display())),
+  };
+  let exchange: Exchange =
+    serde_json::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))?;
+  Ok(Some(exchange.response))
+}
+
+/// Saves an exchange, replacing an earlier recording of the same request.
+pub fn record(dir: &Path, exchange: &Exchange) -> Result<PathBuf, String> {
+  let path = fixture_path(dir, &request_hash(&exchange.system, &exchange.messages));
+  fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+  let text = serde_json::to_string_pretty(exchange).unwrap();
+  fs::write(&path, text).map_err(|err| format!("{}: {err}", path.display()))?;
+  Ok(path)
+}
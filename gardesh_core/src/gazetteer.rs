@@ -0,0 +1,199 @@
+//! Names a prompt may start a trip from (`از میدان ارگ`, `من در دربند هستم`), resolved to a
+//! location. Built from the catalog itself: every place title, the landmarks its manifest lists,
+//! and every neighbourhood at the centre of the places in it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::validate::tag_distance;
+use crate::{normalize, Location, Place, PlaceId};
+
+/// Words naming the kind of a place rather than the place, dropped before names are compared.
+const GENERIC_WORDS: &[&str] = &["محله", "میدان", "خیابان", "چهارراه", "ایستگاه", "مترو"];
+
+/// Largest [`tag_distance`] at which a name is still read as a misspelled gazetteer name.
+const MAX_NAME_DISTANCE: f64 = 0.2;
+
+/// A spot trips start from that is no place of the catalog, such as a square; listed in the
+/// manifest's `landmarks`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Landmark {
+  /// Name as people write it, e.g. `میدان انقلاب`.
+  pub name: String,
+  pub location: Location,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnchorKind {
+  Place { id: PlaceId },
+  Landmark,
+  Neighbourhood,
+}
+
+/// A resolved starting point.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Anchor {
+  /// The title, landmark or neighbourhood name as written in the catalog.
+  pub name: String,
+  #[serde(flatten)]
+  pub kind: AnchorKind,
+  pub location: Location,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+  words: Vec<String>,
+  anchor: Anchor,
+}
+
+/// [`normalize`]d words of a name without [`GENERIC_WORDS`] or punctuation.
+pub(crate) fn name_words(name: &str) -> Vec<String> {
+  normalize(name)
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|f| !f.is_empty() && !GENERIC_WORDS.contains(f))
+    .map(str::to_string)
+    .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Gazetteer {
+  entries: Vec<Entry>,
+}
+
+impl Gazetteer {
+  /// Place titles first, then landmarks, then neighbourhoods; a neighbourhood written as
+  /// `محله ارگ - پامنار` is also known by each of its parts.
+  pub fn build<'a>(places: impl IntoIterator<Item = &'a Place>, landmarks: &[Landmark]) -> Self {
+    let places = places.into_iter().collect::<Vec<_>>();
+    let mut entries = places
+      .iter()
+      .map(|place| Entry {
+        words: name_words(&place.title),
+        anchor: Anchor {
+          name: place.title.clone(),
+          kind: AnchorKind::Place { id: place.id.clone() },
+          location: place.location.clone(),
+        },
+      })
+      .collect::<Vec<_>>();
+    entries.extend(landmarks.iter().map(|landmark| Entry {
+      words: name_words(&landmark.name),
+      anchor: Anchor {
+        name: landmark.name.clone(),
+        kind: AnchorKind::Landmark,
+        location: landmark.location.clone(),
+      },
+    }));
+
+    let mut neighbourhoods = Vec::<(&str, Vec<&Location>)>::new();
+    for place in &places {
+      let name = place.neighbourhood.trim();
+      if name.is_empty() {
+        continue;
+      }
+      match neighbourhoods.iter_mut().find(|(f, _)| *f == name) {
+        Some((_, locations)) => locations.push(&place.location),
+        None => neighbourhoods.push((name, vec![&place.location])),
+      }
+    }
+    for (name, locations) in neighbourhoods {
+      let n = locations.len() as f64;
+      let location = Location {
+        x: locations.iter().map(|f| f.x).sum::<f64>() / n,
+        y: locations.iter().map(|f| f.y).sum::<f64>() / n,
+      };
+      let parts = name.split('-').filter(|_| name.contains('-'));
+      for part in std::iter::once(name).chain(parts) {
+        let anchor = Anchor {
+          name: name.to_string(),
+          kind: AnchorKind::Neighbourhood,
+          location: location.clone(),
+        };
+        entries.push(Entry { words: name_words(part), anchor });
+      }
+    }
+    entries.retain(|f| !f.words.is_empty());
+    Self { entries }
+  }
+
+  /// The place, landmark or neighbourhood `name` refers to: one named exactly so, else the
+  /// shortest name containing all of its words in order (`دارالفنون` for `مدرسه تاریخی
+  /// دارالفنون`), else the closest spelling within [`MAX_NAME_DISTANCE`].
+  pub fn resolve(&self, name: &str) -> Option<&Anchor> {
+    self.lookup(&name_words(name), true)
+  }
+
+  /// [`Gazetteer::resolve`] over words already split by [`name_words`]; `fuzzy` allows the
+  /// spelling fallback.
+  pub(crate) fn lookup(&self, words: &[String], fuzzy: bool) -> Option<&Anchor> {
+    if words.is_empty() {
+      return None;
+    }
+    if let Some(entry) = self.entries.iter().find(|f| f.words == words) {
+      return Some(&entry.anchor);
+    }
+    let contained = self
+      .entries
+      .iter()
+      .filter(|f| f.words.windows(words.len()).any(|window| window == words))
+      .min_by_key(|f| f.words.len());
+    if let Some(entry) = contained {
+      return Some(&entry.anchor);
+    }
+    if !fuzzy {
+      return None;
+    }
+    let joined = words.join(" ");
+    self
+      .entries
+      .iter()
+      .map(|f| (tag_distance(&joined, &f.words.join(" ")), f))
+      .filter(|(distance, _)| *distance <= MAX_NAME_DISTANCE)
+      .min_by(|a, b| a.0.total_cmp(&b.0))
+      .map(|(_, f)| &f.anchor)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::offline::analyze_offline;
+  use crate::testing::catalog;
+
+  fn resolved(name: &str) -> Option<String> {
+    catalog().gazetteer().resolve(name).map(|f| f.name.clone())
+  }
+
+  #[test]
+  fn resolves_titles_exactly_contained_and_misspelled() {
+    assert_eq!(resolved("کاخ گلستان").as_deref(), Some("کاخ گلستان"));
+    assert_eq!(resolved("گلستان").as_deref(), Some("کاخ گلستان"));
+    assert_eq!(resolved("موزه فرش").as_deref(), Some("موزه فرش"));
+    assert_eq!(resolved("عمارت مسعودیة").as_deref(), Some("عمارت مسعودیه"));
+    assert_eq!(resolved("دربند"), None);
+  }
+
+  #[test]
+  fn generic_words_alone_name_nothing() {
+    assert_eq!(resolved("میدان"), None);
+    assert_eq!(resolved("محله خیابان"), None);
+  }
+
+  #[test]
+  fn resolves_landmarks_and_neighbourhoods() {
+    let catalog = catalog();
+    let square = catalog.gazetteer().resolve("میدان انقلاب").unwrap();
+    assert_eq!(square.kind, AnchorKind::Landmark);
+    assert_eq!(square.location, Location { x: 51.3915, y: 35.7009 });
+    let neighbourhood = catalog.gazetteer().resolve("تجریش").unwrap();
+    assert_eq!(neighbourhood.name, "محله تجریش");
+    assert_eq!(neighbourhood.kind, AnchorKind::Neighbourhood);
+  }
+
+  #[test]
+  fn prompts_name_their_start() {
+    let start = |prompt: &str| analyze_offline(&catalog(), prompt).start;
+    assert_eq!(start("از میدان انقلاب شروع کنیم").as_deref(), Some("میدان انقلاب"));
+    assert_eq!(start("من در تجریش هستم").as_deref(), Some("محله تجریش"));
+  }
+}
@@ -0,0 +1,16 @@
+use crate::Location;
+
+/// Great-circle distance between two points, in meters.
+pub fn distance_haversine(loc1: &Location, loc2: &Location) -> f64 {
+  let r = 6371e3; // Earth's radius in meters
+  let phi1 = loc1.y.to_radians();
+  let phi2 = loc2.y.to_radians();
+  let delta_phi = (loc2.y - loc1.y).to_radians();
+  let delta_lambda = (loc2.x - loc1.x).to_radians();
+
+  let a =
+    (delta_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
+  let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+
+  r * c
+}
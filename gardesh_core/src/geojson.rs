@@ -0,0 +1,245 @@
+//! GeoJSON views of the catalog and of itineraries, for inspection and editing in GIS tools.
+
+use derive_more::Display;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::{CatalogManifest, Location, NeshanDataModel, Place, PlaceAttributes, PlaceId};
+
+fn point(location: &Location) -> Value {
+  json!({ "type": "Point", "coordinates": [location.x, location.y] })
+}
+
+fn place_feature(place: &Place) -> Value {
+  let mut feature = json!({
+    "type": "Feature",
+    "id": place.id,
+    "geometry": point(&place.location),
+    "properties": {
+      "title": place.title,
+      "address": place.address,
+      "category": place.category,
+      "type": place.r#type,
+      "region": place.region,
+      "neighbourhood": place.neighbourhood,
+      "tags": place.tags,
+    }
+  });
+  if !place.attributes.is_empty() {
+    feature["properties"]["attributes"] = json!(place.attributes);
+  }
+  feature
+}
+
+/// One point feature per item; the tag pool travels as a foreign member so an export can be
+/// imported back without losing unused tags.
+pub fn catalog_to_geojson(model: &NeshanDataModel) -> Value {
+  json!({
+    "type": "FeatureCollection",
+    "tag_pool": model.tag_pool,
+    "features": model.items.iter().map(place_feature).collect::<Vec<_>>(),
+  })
+}
+
+/// The stops of an itinerary as numbered points, followed by the route connecting them in order.
+pub fn itinerary_to_geojson(stops: &[Place]) -> Value {
+  let mut features = stops
+    .iter()
+    .enumerate()
+    .map(|(i, place)| {
+      let mut feature = place_feature(place);
+      feature["properties"]["stop"] = json!(i + 1);
+      feature
+    })
+    .collect::<Vec<_>>();
+  if stops.len() > 1 {
+    features.push(json!({
+      "type": "Feature",
+      "geometry": {
+        "type": "LineString",
+        "coordinates": stops.iter().map(|f| [f.location.x, f.location.y]).collect::<Vec<_>>(),
+      },
+      "properties": { "route": true },
+    }));
+  }
+  json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[derive(Debug, Display)]
+#[display("{}{message}", feature.map(|f| format!("feature {f}: ")).unwrap_or_default())]
+pub struct GeoJsonError {
+  /// Position of the offending feature, or `None` for problems with the collection itself.
+  pub feature: Option<usize>,
+  pub message: String,
+}
+
+fn string_property(properties: &Map<String, Value>, key: &str) -> Result<String, String> {
+  match properties.get(key) {
+    None | Some(Value::Null) => Ok(String::new()),
+    Some(Value::String(value)) => Ok(value.trim().to_string()),
+    Some(_) => Err(format!("property `{key}` must be a string")),
+  }
+}
+
+fn feature_to_place(feature: &Value, manifest: &CatalogManifest) -> Result<Place, String> {
+  if feature["type"] != "Feature" {
+    return Err("not a Feature".to_string());
+  }
+  if feature["geometry"]["type"] != "Point" {
+    return Err("geometry must be a Point".to_string());
+  }
+  let location = match feature["geometry"]["coordinates"].as_array().map(Vec::as_slice) {
+    Some([x, y, ..]) => match (x.as_f64(), y.as_f64()) {
+      (Some(x), Some(y)) if (-180.0..=180.0).contains(&x) && (-90.0..=90.0).contains(&y) => {
+        Location { x, y }
+      }
+      _ => return Err("coordinates must be a valid longitude and latitude".to_string()),
+    },
+    _ => return Err("coordinates must hold a longitude and a latitude".to_string()),
+  };
+  if let Some(region) = manifest.region.as_ref().filter(|f| !f.contains(&location)) {
+    return Err(format!("point lies outside {}", region.name));
+  }
+  let properties = feature["properties"].as_object().ok_or("missing properties")?;
+  let title = string_property(properties, "title")?;
+  if title.is_empty() {
+    return Err("property `title` is required".to_string());
+  }
+  let kind = string_property(properties, "type")?;
+  let r#type = manifest
+    .place_type_for_source_kind(&kind)
+    .ok_or_else(|| format!("unknown place type `{kind}`"))?;
+  let tags = match properties.get("tags") {
+    None | Some(Value::Null) => Vec::new(),
+    Some(Value::Array(tags)) => tags
+      .iter()
+      .map(|tag| tag.as_str().map(|f| f.trim().to_string()))
+      .collect::<Option<Vec<_>>>()
+      .ok_or("property `tags` must be an array of strings")?,
+    Some(_) => return Err("property `tags` must be an array of strings".to_string()),
+  };
+  let attributes = match properties.get("attributes") {
+    None | Some(Value::Null) => PlaceAttributes::default(),
+    Some(value) => {
+      PlaceAttributes::deserialize(value).map_err(|err| format!("property `attributes`: {err}"))?
+    }
+  };
+  let id = match &feature["id"] {
+    Value::String(id) => PlaceId::from(id.clone()),
+    _ => PlaceId::default(),
+  };
+  let mut place = Place {
+    id,
+    title,
+    address: string_property(properties, "address")?,
+    category: string_property(properties, "category")?,
+    r#type,
+    region: string_property(properties, "region")?,
+    neighbourhood: string_property(properties, "neighbourhood")?,
+    location,
+    tags,
+    attributes,
+  };
+  place.ensure_id();
+  Ok(place)
+}
+
+/// Reads a FeatureCollection of points back into catalog format.
+///
+/// Every feature is validated, points included against the manifest's region, and all problems
+/// are reported together. Route lines from
+/// [`itinerary_to_geojson`] are skipped. Tags used by items but missing from the `tag_pool`
+/// member are appended to the pool.
+pub fn catalog_from_geojson(
+  text: &str,
+  manifest: &CatalogManifest,
+) -> Result<NeshanDataModel, Vec<GeoJsonError>> {
+  let collection_error = |message: String| vec![GeoJsonError { feature: None, message }];
+  let value =
+    serde_json::from_str::<Value>(text).map_err(|err| collection_error(err.to_string()))?;
+  if value["type"] != "FeatureCollection" {
+    return Err(collection_error("not a FeatureCollection".to_string()));
+  }
+  let features = value["features"]
+    .as_array()
+    .ok_or_else(|| collection_error("`features` must be an array".to_string()))?;
+
+  let mut errors = Vec::new();
+  let mut items = Vec::<Place>::new();
+  for (i, feature) in features.iter().enumerate() {
+    if feature["properties"]["route"] == true {
+      continue;
+    }
+    match feature_to_place(feature, manifest) {
+      Ok(place) if items.iter().any(|f| f.id == place.id) => errors
+        .push(GeoJsonError { feature: Some(i), message: format!("duplicate id {}", place.id) }),
+      Ok(place) => items.push(place),
+      Err(message) => errors.push(GeoJsonError { feature: Some(i), message }),
+    }
+  }
+  if !errors.is_empty() {
+    return Err(errors);
+  }
+
+  let mut tag_pool = value["tag_pool"]
+    .as_array()
+    .map(|pool| pool.iter().filter_map(Value::as_str).map(str::to_string).collect::<Vec<_>>())
+    .unwrap_or_default();
+  for tag in items.iter().flat_map(|f| &f.tags) {
+    if !tag_pool.contains(tag) {
+      tag_pool.push(tag.clone());
+    }
+  }
+  Ok(NeshanDataModel { tag_pool, items })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::catalog::Region;
+  use crate::testing::{catalog, manifest, MUSEUM};
+
+  fn museums() -> NeshanDataModel {
+    let catalog = catalog();
+    catalog.categories.iter().find(|f| f.def.place_type.as_str() == MUSEUM).unwrap().data.clone()
+  }
+
+  fn messages(result: Result<NeshanDataModel, Vec<GeoJsonError>>) -> Vec<String> {
+    result.unwrap_err().iter().map(ToString::to_string).collect()
+  }
+
+  #[test]
+  fn export_reads_back_unchanged() {
+    let model = museums();
+    let text = catalog_to_geojson(&model).to_string();
+    let read = catalog_from_geojson(&text, &manifest()).unwrap();
+    assert_eq!(serde_json::to_value(&read).unwrap(), serde_json::to_value(&model).unwrap());
+  }
+
+  #[test]
+  fn rejects_unknown_types_and_duplicate_ids() {
+    let mut value = catalog_to_geojson(&museums());
+    value["features"][0]["properties"]["type"] = json!("سینما");
+    let duplicate = value["features"][1].clone();
+    value["features"].as_array_mut().unwrap().push(duplicate);
+    let errors = messages(catalog_from_geojson(&value.to_string(), &manifest()));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0], "feature 0: unknown place type `سینما`");
+    assert!(errors[1].starts_with("feature 3: duplicate id p"));
+  }
+
+  #[test]
+  fn rejects_points_outside_the_region() {
+    let mut manifest = manifest();
+    manifest.region = Some(Region {
+      name: "تهران".to_string(),
+      south_west: Location { x: 51.2, y: 35.6 },
+      north_east: Location { x: 51.6, y: 35.85 },
+    });
+    let mut value = catalog_to_geojson(&museums());
+    assert!(catalog_from_geojson(&value.to_string(), &manifest).is_ok());
+    value["features"][2]["geometry"] = point(&Location { x: 51.67, y: 32.65 });
+    let errors = messages(catalog_from_geojson(&value.to_string(), &manifest));
+    assert_eq!(errors, ["feature 2: point lies outside تهران"]);
+  }
+}
@@ -0,0 +1,62 @@
+//! GPX 1.1 export of itineraries for navigation apps.
+
+use std::fmt::Write;
+
+use crate::xml::{escape, place_description};
+use crate::Place;
+
+fn point(tag: &str, index: usize, place: &Place) -> String {
+  format!(
+    "<{tag} lat=\"{}\" lon=\"{}\"><name>{}. {}</name><desc>{}</desc><type>{}</type></{tag}>",
+    place.location.y,
+    place.location.x,
+    index + 1,
+    escape(&place.title),
+    escape(&place_description(place)),
+    escape(place.r#type.as_str()),
+  )
+}
+
+/// The stops as waypoints plus a route visiting them in order.
+pub fn itinerary_to_gpx(name: &str, stops: &[Place]) -> String {
+  let mut gpx = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  gpx +=
+    "<gpx version=\"1.1\" creator=\"tauri_gardesh\" xmlns=\"http://www.topografix.com/GPX/1/1\">\n";
+  writeln!(gpx, "  <metadata><name>{}</name></metadata>", escape(name)).unwrap();
+  for (i, place) in stops.iter().enumerate() {
+    writeln!(gpx, "  {}", point("wpt", i, place)).unwrap();
+  }
+  writeln!(gpx, "  <rte>\n    <name>{}</name>", escape(name)).unwrap();
+  for (i, place) in stops.iter().enumerate() {
+    writeln!(gpx, "    {}", point("rtept", i, place)).unwrap();
+  }
+  gpx += "  </rte>\n</gpx>\n";
+  gpx
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::catalog;
+  use crate::xml::tests::{parse, RESERVED};
+
+  #[test]
+  fn export_is_well_formed_with_reserved_characters() {
+    let catalog = catalog();
+    let mut stops = catalog.places().take(2).cloned().collect::<Vec<_>>();
+    stops[0].title = RESERVED.to_string();
+    let gpx = itinerary_to_gpx(RESERVED, &stops);
+    let document = parse(&gpx);
+    let names = document
+      .descendants()
+      .filter(|f| f.has_tag_name("name"))
+      .filter_map(|f| f.text())
+      .collect::<Vec<_>>();
+    // The metadata and the route are named after the itinerary, each point after its stop.
+    let points = [format!("1. {RESERVED}"), format!("2. {}", stops[1].title)];
+    let points = points.iter().map(String::as_str);
+    let expected = [RESERVED].into_iter().chain(points.clone()).chain([RESERVED]).chain(points);
+    assert_eq!(names, expected.collect::<Vec<_>>());
+    assert_eq!(document.descendants().filter(|f| f.has_tag_name("rtept")).count(), 2);
+  }
+}
@@ -0,0 +1,67 @@
+//! KML 2.2 export of itineraries for navigation apps and Google Earth.
+
+use std::fmt::Write;
+
+use crate::xml::{escape, place_description};
+use crate::Place;
+
+/// One placemark per stop plus a line placemark for the route.
+pub fn itinerary_to_kml(name: &str, stops: &[Place]) -> String {
+  let mut kml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+  kml += "<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n";
+  writeln!(kml, "  <name>{}</name>", escape(name)).unwrap();
+  for (i, place) in stops.iter().enumerate() {
+    writeln!(
+      kml,
+      "  <Placemark><name>{}. {}</name><description>{}</description>\
+       <Point><coordinates>{},{}</coordinates></Point></Placemark>",
+      i + 1,
+      escape(&place.title),
+      escape(&place_description(place)),
+      place.location.x,
+      place.location.y,
+    )
+    .unwrap();
+  }
+  if stops.len() > 1 {
+    let coordinates = stops
+      .iter()
+      .map(|f| format!("{},{}", f.location.x, f.location.y))
+      .collect::<Vec<_>>()
+      .join(" ");
+    writeln!(
+      kml,
+      "  <Placemark><name>{}</name><LineString><tessellate>1</tessellate>\
+       <coordinates>{coordinates}</coordinates></LineString></Placemark>",
+      escape(name),
+    )
+    .unwrap();
+  }
+  kml += "</Document>\n</kml>\n";
+  kml
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::catalog;
+  use crate::xml::tests::{parse, RESERVED};
+
+  #[test]
+  fn export_is_well_formed_with_reserved_characters() {
+    let catalog = catalog();
+    let mut stops = catalog.places().take(2).cloned().collect::<Vec<_>>();
+    stops[0].title = RESERVED.to_string();
+    let kml = itinerary_to_kml(RESERVED, &stops);
+    let document = parse(&kml);
+    let names = document
+      .descendants()
+      .filter(|f| f.has_tag_name("name"))
+      .filter_map(|f| f.text())
+      .collect::<Vec<_>>();
+    assert_eq!(
+      names,
+      [RESERVED, &format!("1. {RESERVED}"), &format!("2. {}", stops[1].title), RESERVED]
+    );
+  }
+}
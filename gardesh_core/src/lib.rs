@@ -0,0 +1,43 @@
+pub mod analysis;
+pub mod analyzer;
+pub mod cache;
+pub mod catalog;
+pub mod constraints;
+pub mod conversation;
+pub mod eval;
+pub mod fixture;
+pub mod gazetteer;
+pub mod geo;
+pub mod geojson;
+pub mod gpx;
+pub mod kml;
+#[cfg(feature = "llm")]
+pub mod llm;
+pub mod normalize;
+pub mod offline;
+pub mod place;
+pub mod plan;
+pub mod prefilter;
+pub mod prompt;
+pub mod quality;
+pub mod spatial;
+#[cfg(test)]
+mod testing;
+pub mod validate;
+mod xml;
+
+pub use analysis::{PlaceInfo, PromptAnalyses, StopOrder};
+pub use analyzer::{AnalyzeError, LlmConfig, LlmProvider, PromptAnalyzer};
+pub use cache::{AnalysisCache, CachePolicy};
+pub use catalog::{CatalogError, CatalogManifest, PlaceCatalog};
+pub use constraints::{Constraints, PlaceAttributes, UnmetConstraint};
+pub use conversation::{apply_edits, ChatMessage, ChatRole, PlanEdit};
+pub use fixture::FixtureMode;
+pub use geo::distance_haversine;
+#[cfg(feature = "llm")]
+pub use llm::{CheckedAnalyses, CheckedEdits, LlmAnalyzer};
+pub use normalize::normalize;
+pub use offline::OfflineAnalyzer;
+pub use place::{Location, NeshanDataModel, Place, PlaceId, PlaceScoring, PlaceType};
+pub use plan::{plan, plan_checked, CheckedPlan, PlanError, PlanStop};
+pub use validate::{repair, TagCorrection, TagReport};
@@ -0,0 +1,276 @@
+use genai::adapter::AdapterKind;
+use genai::chat::{ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, JsonSpec};
+use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
+use genai::{Client, ModelIden, ServiceTarget};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::Proxy;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::analyzer::{AnalyzeError, LlmConfig, LlmProvider, PromptAnalyzer};
+use crate::conversation::{self, describe_plan, ChatRole, PlanEdit, PlanEdits, MAX_HISTORY};
+use crate::fixture::{self, Exchange, FixtureMode};
+use crate::prefilter::{LexicalSimilarity, PromptSavings, TagSelection};
+use crate::prompt::{edits_schema, refine_system_prompt, response_schema, system_prompt};
+use crate::validate::{repair, repair_edits, TagReport};
+use crate::{normalize, PlaceCatalog, PlanStop, PromptAnalyses};
+
+/// [`PromptAnalyzer`] backed by a chat model reached through `genai`.
+#[derive(Debug, Clone)]
+pub struct LlmAnalyzer {
+  config: LlmConfig,
+  client: Client,
+}
+
+/// HTTP client honoring the config's proxy, timeout and extra headers.
+fn http_client(config: &LlmConfig) -> Result<reqwest::Client, String> {
+  let mut headers = HeaderMap::new();
+  for (name, value) in &config.headers {
+    let name =
+      HeaderName::from_bytes(name.trim().as_bytes()).map_err(|err| format!("{name}: {err}"))?;
+    let value = HeaderValue::from_str(value.trim()).map_err(|err| format!("{name}: {err}"))?;
+    headers.insert(name, value);
+  }
+  let mut builder = reqwest::Client::builder().timeout(config.timeout()).default_headers(headers);
+  if let Some(proxy) = config.proxy() {
+    builder = builder.proxy(Proxy::all(proxy).map_err(|err| format!("proxy {proxy}: {err}"))?);
+  }
+  builder.build().map_err(|err| err.to_string())
+}
+
+impl LlmAnalyzer {
+  pub fn new(config: LlmConfig) -> Result<Self, AnalyzeError> {
+    let reqwest_client = http_client(&config)
+      .map_err(|message| AnalyzeError::Config { analyzer: config.label(), message })?;
+    let target = config.clone();
+    // The resolver pins every request to the configured endpoint, whatever the model name looks
+    // like; genai would otherwise guess the adapter from the name.
+    let target_resolver = ServiceTargetResolver::from_resolver_fn(
+      move |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
+        let ServiceTarget { model, .. } = service_target;
+        let endpoint = Endpoint::from_owned(target.base_url());
+        let auth = match (target.provider, target.api_key()) {
+          (LlmProvider::Ollama, key) if key.is_empty() => AuthData::from_single("ollama"),
+          (_, key) => AuthData::from_single(key),
+        };
+        let model = ModelIden::new(adapter_kind(target.provider), model.model_name);
+        Ok(ServiceTarget { endpoint, auth, model })
+      },
+    );
+    let client = Client::builder()
+      .with_reqwest(reqwest_client)
+      .with_service_target_resolver(target_resolver)
+      .build();
+    Ok(Self { config, client })
+  }
+
+  pub fn config(&self) -> &LlmConfig {
+    &self.config
+  }
+
+  /// The model's raw reply to `messages`, or its recording under [`FixtureMode::Replay`].
+  async fn chat(
+    &self,
+    system: &str,
+    messages: &[conversation::ChatMessage],
+    schema: Value,
+  ) -> Result<String, AnalyzeError> {
+    let fixture_error = |message| AnalyzeError::Fixture { analyzer: self.name(), message };
+    let dir = self.config.fixture_dir();
+    if self.config.fixture_mode == FixtureMode::Replay {
+      return fixture::replay(&dir, system, messages).map_err(fixture_error)?.ok_or_else(|| {
+        let hash = fixture::request_hash(system, messages);
+        fixture_error(format!("no recording {hash} in {}", dir.display()))
+      });
+    }
+
+    let chat_req = ChatRequest::new(
+      std::iter::once(ChatMessage::system(system))
+        .chain(messages.iter().map(|f| match f.role {
+          ChatRole::User => ChatMessage::user(f.content.clone()),
+          ChatRole::Assistant => ChatMessage::assistant(f.content.clone()),
+        }))
+        .collect(),
+    );
+    let options = ChatOptions::default()
+      .with_max_tokens(1000)
+      .with_response_format(ChatResponseFormat::JsonSpec(JsonSpec::new("items", schema)));
+    let chat_res = self
+      .client
+      .exec_chat(self.config.model(), chat_req, Some(&options))
+      .await
+      .map_err(|err| AnalyzeError::Request { analyzer: self.name(), message: err.to_string() })?;
+    let text = chat_res
+      .content_text_into_string()
+      .ok_or_else(|| AnalyzeError::EmptyResponse { analyzer: self.name() })?;
+
+    if self.config.fixture_mode == FixtureMode::Record {
+      let exchange = Exchange {
+        system: system.to_string(),
+        messages: messages.to_vec(),
+        response: text.clone(),
+      };
+      fixture::record(&dir, &exchange).map_err(fixture_error)?;
+    }
+    Ok(text)
+  }
+
+  /// Sends `messages` and returns the raw reply along with its parsed value.
+  async fn ask<T: DeserializeOwned>(
+    &self,
+    system: &str,
+    messages: &[conversation::ChatMessage],
+    schema: Value,
+  ) -> Result<(String, T), AnalyzeError> {
+    let text = self.chat(system, messages, schema).await?;
+    let value = serde_json::from_str(&text)
+      .map_err(|source| AnalyzeError::Parse { analyzer: self.name(), source })?;
+    Ok((text, value))
+  }
+
+  /// Analyzes `prompt` and [`repair`]s the result against the catalog. When nothing valid is
+  /// left and [`LlmConfig::reask_on_invalid`] is set, the model is asked once more with the
+  /// list of its mistakes, and that second answer is returned instead.
+  pub async fn analyze_checked(
+    &self,
+    catalog: &PlaceCatalog,
+    prompt: &str,
+  ) -> Result<CheckedAnalyses, AnalyzeError> {
+    let selection = self.tag_selection(catalog, prompt);
+    let system = system_prompt(catalog, &selection);
+    let mut messages = vec![conversation::ChatMessage::user(normalize(prompt))];
+    let (text, mut analyses) = self.ask(&system, &messages, response_schema(catalog)).await?;
+    let report = repair(catalog, &mut analyses);
+    if !(report.nothing_valid && self.config.reask_on_invalid) {
+      let prompt_size = PromptSavings::measure(catalog, &selection);
+      return Ok(CheckedAnalyses { analyses, report, reasked: false, prompt_size });
+    }
+    messages.push(conversation::ChatMessage::assistant(text));
+    messages.push(conversation::ChatMessage::user(report.feedback()));
+    let (_, mut analyses) = self.ask(&system, &messages, response_schema(catalog)).await?;
+    let report = repair(catalog, &mut analyses);
+    let prompt_size = PromptSavings::measure(catalog, &selection);
+    Ok(CheckedAnalyses { analyses, report, reasked: true, prompt_size })
+  }
+
+  /// Reads a follow-up as edits to `stops` and [`repair_edits`] them, re-asking as
+  /// [`LlmAnalyzer::analyze_checked`] does when nothing valid is left.
+  pub async fn refine_checked(
+    &self,
+    catalog: &PlaceCatalog,
+    history: &[conversation::ChatMessage],
+    stops: &[PlanStop],
+    follow_up: &str,
+  ) -> Result<CheckedEdits, AnalyzeError> {
+    let earlier = history.iter().skip(history.len().saturating_sub(MAX_HISTORY)).cloned();
+    let request =
+      format!("برنامه فعلی:\n{}\n\nدرخواست: {}", describe_plan(stops), normalize(follow_up));
+    let system = refine_system_prompt(catalog, &self.tag_selection(catalog, &request));
+    let mut messages =
+      earlier.chain([conversation::ChatMessage::user(request)]).collect::<Vec<_>>();
+    let (text, PlanEdits { mut edits }) =
+      self.ask(&system, &messages, edits_schema(catalog)).await?;
+    let report = repair_edits(catalog, &mut edits);
+    if !(report.nothing_valid && self.config.reask_on_invalid) {
+      return Ok(CheckedEdits { edits, report, reasked: false });
+    }
+    messages.push(conversation::ChatMessage::assistant(text));
+    messages.push(conversation::ChatMessage::user(report.feedback()));
+    let (_, PlanEdits { mut edits }) = self.ask(&system, &messages, edits_schema(catalog)).await?;
+    let report = repair_edits(catalog, &mut edits);
+    Ok(CheckedEdits { edits, report, reasked: true })
+  }
+
+  /// Tags of each pool worth listing for `text`, as limited by [`LlmConfig::prompt_tags`].
+  pub fn tag_selection(&self, catalog: &PlaceCatalog, text: &str) -> TagSelection {
+    TagSelection::select(catalog, text, self.config.prompt_tags, &LexicalSimilarity)
+  }
+}
+
+/// Result of [`LlmAnalyzer::analyze_checked`]; `report` describes the returned analyses, so
+/// after a re-ask it covers the second answer only.
+#[derive(Debug, Clone)]
+pub struct CheckedAnalyses {
+  pub analyses: PromptAnalyses,
+  pub report: TagReport,
+  pub reasked: bool,
+  /// System prompt size with and without the [`LlmAnalyzer::tag_selection`].
+  pub prompt_size: PromptSavings,
+}
+
+/// Result of [`LlmAnalyzer::refine_checked`]; like [`CheckedAnalyses`], `report` covers the
+/// returned edits only.
+#[derive(Debug, Clone)]
+pub struct CheckedEdits {
+  pub edits: Vec<PlanEdit>,
+  pub report: TagReport,
+  pub reasked: bool,
+}
+
+fn adapter_kind(provider: LlmProvider) -> AdapterKind {
+  match provider {
+    LlmProvider::Gemini => AdapterKind::Gemini,
+    LlmProvider::OpenAiCompatible => AdapterKind::OpenAI,
+    LlmProvider::Ollama => AdapterKind::Ollama,
+  }
+}
+
+impl PromptAnalyzer for LlmAnalyzer {
+  fn name(&self) -> String {
+    self.config.label()
+  }
+
+  async fn analyze(
+    &self,
+    catalog: &PlaceCatalog,
+    prompt: &str,
+  ) -> Result<PromptAnalyses, AnalyzeError> {
+    self.analyze_checked(catalog, prompt).await.map(|f| f.analyses)
+  }
+
+  async fn refine(
+    &self,
+    catalog: &PlaceCatalog,
+    history: &[conversation::ChatMessage],
+    stops: &[PlanStop],
+    follow_up: &str,
+  ) -> Result<Vec<PlanEdit>, AnalyzeError> {
+    self.refine_checked(catalog, history, stops, follow_up).await.map(|f| f.edits)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::plan_checked;
+  use crate::testing::{catalog, place_type, MUSEUM, RESTAURANT};
+  use crate::validate::TagCorrection;
+
+  /// Recordings of [`crate::testing::catalog`] prompts, made against `fake_gemini`.
+  const FIXTURE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/llm");
+
+  #[tokio::test]
+  async fn replayed_analysis_plans_the_requested_stops() {
+    let config = LlmConfig {
+      fixture_mode: FixtureMode::Replay,
+      fixture_dir: Some(FIXTURE_DIR.to_string()),
+      ..LlmConfig::new(LlmProvider::Gemini)
+    };
+    let catalog = catalog();
+    let analyzer = LlmAnalyzer::new(config).unwrap();
+    let checked = analyzer.analyze_checked(&catalog, "دو موزه و یک رستوران سنتی").await.unwrap();
+    // The recording asks for a `رستوران سنتی` tag, which is in no pool of the test catalog.
+    let dropped = TagCorrection::Dropped {
+      place_type: place_type(RESTAURANT),
+      tag: "رستوران سنتی".to_string(),
+    };
+    assert_eq!(checked.report.corrections, [dropped]);
+    let plan = plan_checked(&catalog, &checked.analyses).unwrap();
+    let stops = plan.stops.iter().map(|f| &f.candidates[0]).collect::<Vec<_>>();
+    assert_eq!(stops.iter().filter(|f| f.r#type == place_type(MUSEUM)).count(), 2);
+    let restaurants =
+      stops.iter().filter(|f| f.r#type == place_type(RESTAURANT)).collect::<Vec<_>>();
+    assert_eq!(restaurants.len(), 1);
+    assert!(restaurants[0].tags.contains(&"سنتی".to_string()));
+  }
+}
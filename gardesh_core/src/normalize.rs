@@ -0,0 +1,105 @@
+//! Folding of Persian text so that catalog data and user input compare equal regardless of the
+//! keyboard layout or typing habits that produced them.
+
+/// Normalizes text for comparison; the result is not meant to be shown to users.
+///
+/// - Arabic letter forms are folded to their Persian equivalents (`ي` → `ی`, `ك` → `ک`, …).
+/// - Zero-width non-joiners and other invisible joiners become a space, and runs of whitespace
+///   collapse to one, so `سنگ‌قبرها` and `سنگ قبرها` are the same.
+/// - Diacritics (harakat, tanwin, superscript alef) and tatweel are removed.
+/// - Persian and Arabic-Indic digits become ASCII digits.
+/// - Latin letters are lowercased.
+pub fn normalize(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut pending_space = false;
+  for c in text.chars() {
+    let c = match c {
+      'ي' | 'ى' | 'ئ' => 'ی',
+      'ك' => 'ک',
+      'ة' | 'ۀ' => 'ه',
+      'أ' | 'إ' | 'ٱ' => 'ا',
+      'ؤ' => 'و',
+      '۰'..='۹' => char::from(b'0' + (c as u32 - '۰' as u32) as u8),
+      '٠'..='٩' => char::from(b'0' + (c as u32 - '٠' as u32) as u8),
+      // Tatweel and diacritics.
+      'ـ' | '\u{064B}'..='\u{065F}' | '\u{0670}' => continue,
+      // ZWNJ, ZWJ, direction marks and soft hyphen separate words like a space does.
+      '\u{200C}' | '\u{200D}' | '\u{200E}' | '\u{200F}' | '\u{00AD}' => ' ',
+      c => c,
+    };
+    if c.is_whitespace() {
+      pending_space = !out.is_empty();
+      continue;
+    }
+    if pending_space {
+      out.push(' ');
+      pending_space = false;
+    }
+    out.extend(c.to_lowercase());
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::normalize;
+
+  #[test]
+  fn folds_arabic_letters() {
+    for (input, expected) in [
+      ("علي", "علی"),
+      ("كباب", "کباب"),
+      ("مؤسسه", "موسسه"),
+      ("أحمد إمام", "احمد امام"),
+      ("ٱلله", "الله"),
+      ("مدرسة", "مدرسه"),
+      ("خانۀ", "خانه"),
+      ("موسى", "موسی"),
+      ("رئیس", "رییس"),
+    ] {
+      assert_eq!(normalize(input), expected, "{input}");
+    }
+  }
+
+  #[test]
+  fn drops_diacritics_and_tatweel() {
+    for (input, expected) in [
+      ("کِتابْ", "کتاب"),
+      ("مُحَمَّد", "محمد"),
+      ("حتماً", "حتما"),
+      ("رحمٰن", "رحمن"),
+      ("تهـــران", "تهران"),
+    ] {
+      assert_eq!(normalize(input), expected, "{input}");
+    }
+  }
+
+  #[test]
+  fn joiners_separate_words() {
+    for (input, expected) in [
+      ("سنگ\u{200C}قبرها", "سنگ قبرها"),
+      ("سنگ قبرها", "سنگ قبرها"),
+      ("می\u{200D}خواهم", "می خواهم"),
+      ("\u{200F}موزه\u{200E}", "موزه"),
+      ("  دو   موزه \n", "دو موزه"),
+      ("a\u{00AD}b", "a b"),
+    ] {
+      assert_eq!(normalize(input), expected, "{input:?}");
+    }
+  }
+
+  #[test]
+  fn folds_digits_and_latin_case() {
+    for (input, expected) in
+      [("۳ موزه", "3 موزه"), ("٤٥", "45"), ("۰۱۲۳۴۵۶۷۸۹", "0123456789"), ("Cafe DAR", "cafe dar")]
+    {
+      assert_eq!(normalize(input), expected, "{input}");
+    }
+  }
+
+  #[test]
+  fn is_idempotent() {
+    let text = "يك كافه‌ي سنّتي ۲ طبقه";
+    assert_eq!(normalize(&normalize(text)), normalize(text));
+  }
+}
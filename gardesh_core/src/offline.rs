@@ -0,0 +1,539 @@
+//! Prompt analysis without a model: keywords from the category registry, tag pools matched as
+//! phrases, counts read from digits or Persian number words, and constraints and visiting order
+//! from fixed word lists.
+
+use std::collections::HashSet;
+use std::future::Future;
+use std::ops::Range;
+
+use crate::analyzer::{AnalyzeError, PromptAnalyzer};
+use crate::constraints::{Diet, PriceLevel};
+use crate::conversation::{ChatMessage, PlanEdit};
+use crate::gazetteer::{name_words, Anchor};
+use crate::{normalize, Constraints, PlaceCatalog, PlaceInfo, PlanStop, PromptAnalyses, StopOrder};
+
+/// Deterministic [`PromptAnalyzer`] used when no model is configured or reachable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OfflineAnalyzer;
+
+impl PromptAnalyzer for OfflineAnalyzer {
+  fn name(&self) -> String {
+    "offline".to_string()
+  }
+
+  fn analyze(
+    &self,
+    catalog: &PlaceCatalog,
+    prompt: &str,
+  ) -> impl Future<Output = Result<PromptAnalyses, AnalyzeError>> {
+    std::future::ready(Ok(analyze_offline(catalog, prompt)))
+  }
+
+  fn refine(
+    &self,
+    catalog: &PlaceCatalog,
+    _history: &[ChatMessage],
+    stops: &[PlanStop],
+    follow_up: &str,
+  ) -> impl Future<Output = Result<Vec<PlanEdit>, AnalyzeError>> {
+    std::future::ready(Ok(refine_offline(catalog, stops, follow_up)))
+  }
+}
+
+const NUMBER_WORDS: &[(&str, u32)] = &[
+  ("یک", 1),
+  ("یه", 1),
+  ("دو", 2),
+  ("سه", 3),
+  ("چهار", 4),
+  ("پنج", 5),
+  ("شش", 6),
+  ("شیش", 6),
+  ("هفت", 7),
+  ("هشت", 8),
+];
+
+/// Number words that are also everyday words (`نه` is "no", `ده` "village"), read as numbers only
+/// before a classifier, as in `نه تا موزه`; `نه، موزه نمیخوام` asks for no count.
+const CLASSIFIED_NUMBER_WORDS: &[(&str, u32)] = &[("نه", 9), ("ده", 10)];
+
+/// Counting words that may sit between a number and its noun, as in `سه تا موزه`.
+const CLASSIFIERS: &[&str] = &["تا", "عدد", "مورد"];
+
+/// Nouns that count stops of any category, as in `چهار جا` or `۳ مکان`.
+const STOP_NOUNS: &[&str] = &["جا", "جای", "مکان", "نقطه", "مقصد"];
+
+/// Endings a keyword may carry when written without a space, as in `موزه‌ای` or `رستورانها`.
+const KEYWORD_SUFFIXES: &[&str] = &["ها", "های", "هایی", "ی", "ای"];
+
+/// Verbs of a follow-up that swaps a stop, as in `رستوران رو عوض کن` or `به جای موزه یه رستوران`.
+const REPLACE_WORDS: &[&str] = &["عوض", "به جای", "بجای", "تغییر", "جایگزین", "تعویض"];
+
+/// Verbs of a follow-up that drops a stop.
+const REMOVE_WORDS: &[&str] = &["حذف", "بردار", "نمیخوام", "نمی خوام", "خط بزن"];
+
+/// Object endings verbs may carry, as in `عوضش` or `حذفشون`.
+const VERB_SUFFIXES: &[&str] = &["ش", "شو", "شون", "شان", "ید"];
+
+/// Words a starting point follows, as in `از دربند` or `نزدیک میدان ارگ`.
+const START_BEFORE: &[&str] = &["از", "نزدیک", "کنار", "اطراف", "حوالی"];
+
+/// Words a starting point precedes, as in `من دربند هستم`.
+const START_AFTER: &[&str] = &["هستم", "هستیم"];
+
+/// Longest starting point tried, in words.
+const MAX_START_WORDS: usize = 4;
+
+/// Words asking for cheap places, as in `یه ناهار ارزون`.
+const CHEAP_WORDS: &[&str] = &["ارزان", "ارزون", "اقتصادی", "مقرون به صرفه"];
+
+const DIET_WORDS: &[(&str, Diet)] = &[
+  ("گیاهی", Diet::Vegetarian),
+  ("گیاهخوار", Diet::Vegetarian),
+  ("گیاه خوار", Diet::Vegetarian),
+  ("بدون گوشت", Diet::Vegetarian),
+  ("وگان", Diet::Vegan),
+  ("بدون گلوتن", Diet::GlutenFree),
+];
+
+const WHEELCHAIR_WORDS: &[&str] =
+  &["ویلچر", "ویلچیر", "صندلی چرخدار", "صندلی چرخ دار", "معلول", "کم توان", "کمتوان"];
+
+/// Units of a time budget in minutes, as in `سه ساعت وقت دارم`; `و نیم` after one adds half an
+/// hour, and `نیم ساعت` alone is thirty minutes.
+const TIME_UNITS: &[(&str, u32)] = &[("ساعت", 60), ("دقیقه", 1)];
+
+/// Words putting a request first, as in `اول یه خانه تاریخی`.
+const FIRST_WORDS: &[&str] = &["اول", "اولش", "ابتدا", "نخست"];
+
+/// Words putting a request last, as in `آخرش شام`.
+const LAST_WORDS: &[&str] = &["آخر", "آخرش", "آخرسر", "پایان"];
+
+/// Words putting a request after the one named before it, as in `بعد ناهار`.
+const THEN_WORDS: &[&str] = &["بعد", "بعدش", "بعدشم", "بعدم", "سپس", "بعدا"];
+
+/// Tags only accept plural endings; `ی` would turn the tag `تاریخ` into the word `تاریخی`.
+const TAG_SUFFIXES: &[&str] = &["ها", "های", "هایی"];
+
+fn split_words(text: &str) -> Vec<String> {
+  normalize(text)
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|f| !f.is_empty())
+    .map(str::to_string)
+    .collect()
+}
+
+fn token_matches(token: &str, word: &str, suffixes: &[&str]) -> bool {
+  token == word || token.strip_prefix(word).is_some_and(|rest| suffixes.contains(&rest))
+}
+
+/// Every span of `tokens` spelling `phrase`; only the last word may carry one of `suffixes`.
+fn find_phrase(tokens: &[String], phrase: &[String], suffixes: &[&str]) -> Vec<Range<usize>> {
+  if phrase.is_empty() || phrase.len() > tokens.len() {
+    return Vec::new();
+  }
+  (0..=tokens.len() - phrase.len())
+    .filter(|&start| {
+      phrase.iter().enumerate().all(|(i, word)| {
+        let suffixes = if i == phrase.len() - 1 { suffixes } else { &[] };
+        token_matches(&tokens[start + i], word, suffixes)
+      })
+    })
+    .map(|start| start..start + phrase.len())
+    .collect()
+}
+
+fn number_word(words: &[(&str, u32)], token: &str) -> Option<u32> {
+  words.iter().find(|(word, _)| *word == token).map(|(_, n)| *n)
+}
+
+fn number(token: &str) -> Option<u32> {
+  token
+    .parse::<u32>()
+    .ok()
+    .or_else(|| number_word(NUMBER_WORDS, token))
+    .filter(|n| (1..=20).contains(n))
+}
+
+/// The count written right before `start`, skipping one classifier; the words of
+/// [`CLASSIFIED_NUMBER_WORDS`] only count with one.
+fn count_before(tokens: &[String], start: usize) -> Option<u32> {
+  let mut at = start.checked_sub(1)?;
+  let classified = CLASSIFIERS.contains(&tokens[at].as_str());
+  if classified {
+    at = at.checked_sub(1)?;
+  }
+  let token = &tokens[at];
+  number(token).or_else(|| number_word(CLASSIFIED_NUMBER_WORDS, token).filter(|_| classified))
+}
+
+/// The starting point named next to one of [`START_BEFORE`] or [`START_AFTER`], longest name
+/// first, with the span of `tokens` naming it.
+///
+/// `بعد از موزه` is not a start: a name made of a category keyword and at most one other word
+/// must be a whole place title or neighbourhood, and a lone keyword is never one.
+fn find_start<'a>(
+  catalog: &'a PlaceCatalog,
+  tokens: &[String],
+) -> Option<(Range<usize>, &'a Anchor)> {
+  let keywords = catalog
+    .categories
+    .iter()
+    .flat_map(|f| {
+      [f.def.place_type.as_str(), f.def.label_fa_plural.as_str()]
+        .into_iter()
+        .chain(f.def.keywords.iter().map(String::as_str))
+    })
+    .flat_map(split_words)
+    .collect::<HashSet<_>>();
+  let resolve = |span: Range<usize>| {
+    let words = name_words(&tokens[span.clone()].join(" "));
+    let generic = words.iter().any(|f| keywords.contains(f));
+    if generic && words.len() < 2 {
+      return None;
+    }
+    let anchor = catalog.gazetteer().lookup(&words, !generic)?;
+    Some((span, anchor))
+  };
+  (0..tokens.len()).find_map(|i| {
+    let spans: Vec<Range<usize>> = if START_BEFORE.contains(&tokens[i].as_str()) {
+      (1..=MAX_START_WORDS).rev().map(|n| i + 1..(i + 1 + n).min(tokens.len())).collect()
+    } else if START_AFTER.contains(&tokens[i].as_str()) {
+      (1..=MAX_START_WORDS).rev().map(|n| i.saturating_sub(n)..i).collect()
+    } else {
+      vec![]
+    };
+    spans.into_iter().filter(|f| !f.is_empty()).find_map(resolve)
+  })
+}
+
+/// Constraints named in `tokens`, with the words naming them cleared so they are not read as
+/// tags (`ساعت` is one in the museum pool).
+fn take_constraints(tokens: &mut [String]) -> Constraints {
+  let mut constraints = Constraints::default();
+  let mut spans = Vec::new();
+  let mut find = |tokens: &[String], phrase: &str| {
+    let found = find_phrase(tokens, &split_words(phrase), KEYWORD_SUFFIXES);
+    let any = !found.is_empty();
+    spans.extend(found);
+    any
+  };
+  if CHEAP_WORDS.iter().any(|f| find(tokens, f)) {
+    constraints.max_price = Some(PriceLevel::Cheap);
+  }
+  for (phrase, diet) in DIET_WORDS {
+    if find(tokens, phrase) && !constraints.diets.contains(diet) {
+      constraints.diets.push(*diet);
+    }
+  }
+  constraints.wheelchair = WHEELCHAIR_WORDS.iter().any(|f| find(tokens, f));
+
+  let budget = (0..tokens.len()).find_map(|i| {
+    let (_, minutes) = TIME_UNITS.iter().find(|(unit, _)| tokens[i] == *unit)?;
+    let at = i.checked_sub(1)?;
+    let n = match tokens[at].as_str() {
+      "نیم" => return Some((at..i + 1, Some(30))),
+      token => token.parse::<u32>().ok().or_else(|| number(token))?,
+    };
+    let half = *minutes == 60 && tokens.get(i + 1..i + 3).is_some_and(|f| f == ["و", "نیم"]);
+    // A budget of no time, or too long to count in minutes, is no constraint, but its words are
+    // still taken.
+    Some(match half {
+      true => (at..i + 3, n.checked_mul(60).and_then(|f| f.checked_add(30))),
+      false => (at..i + 1, n.checked_mul(*minutes)),
+    })
+  });
+  if let Some((span, minutes)) = budget {
+    spans.push(span);
+    constraints.time_budget_minutes = minutes.filter(|f| *f > 0);
+  }
+  for span in spans {
+    tokens[span].iter_mut().for_each(String::clear);
+  }
+  constraints
+}
+
+struct Detected {
+  first: usize,
+  /// Where each of the category's keywords starts in the prompt.
+  keywords_at: Vec<usize>,
+  count: Option<u32>,
+  tags: Vec<String>,
+}
+
+/// Visiting order of each of `detected`, in prompt order.
+///
+/// `بعد از موزه` and `قبل از موزه` place the next request of another category after or before
+/// the museums, or the previous one when none follows (`رستوران را بعد از موزه برو`). Otherwise
+/// the words since the previous request decide: one of [`FIRST_WORDS`] or [`LAST_WORDS`] fixes
+/// the request first or last, and one of [`THEN_WORDS`] puts it after the previous request.
+fn read_orders(
+  catalog: &PlaceCatalog,
+  tokens: &[String],
+  detected: &[(usize, Detected)],
+) -> Vec<StopOrder> {
+  let place_type = |k: usize| catalog.categories[detected[k].0].def.place_type.clone();
+  let mut orders = vec![StopOrder::Flexible; detected.len()];
+  let mut relative = HashSet::new();
+  for i in 0..tokens.len().saturating_sub(2) {
+    let before = match tokens[i].as_str() {
+      "قبل" => true,
+      "بعد" => false,
+      _ => continue,
+    };
+    if tokens[i + 1] != "از" {
+      continue;
+    }
+    relative.extend([i, i + 1]);
+    let Some(reference) = detected.iter().position(|(_, f)| f.keywords_at.contains(&(i + 2)))
+    else {
+      continue;
+    };
+    let others = || (0..detected.len()).filter(move |&k| k != reference);
+    let subject = others()
+      .find(|&k| detected[k].1.first > i + 2)
+      .or(others().rfind(|&k| detected[k].1.first < i));
+    if let Some(subject) = subject {
+      orders[subject] = match before {
+        true => StopOrder::Before { place_type: place_type(reference) },
+        false => StopOrder::After { place_type: place_type(reference) },
+      };
+    }
+  }
+  for k in 0..detected.len() {
+    if !orders[k].is_flexible() {
+      continue;
+    }
+    let since = k.checked_sub(1).map_or(0, |previous| detected[previous].1.first + 1);
+    let words = (since..detected[k].1.first).filter(|i| !relative.contains(i)).map(|i| &tokens[i]);
+    for word in words {
+      if FIRST_WORDS.contains(&word.as_str()) {
+        orders[k] = StopOrder::Position { position: 1 };
+      } else if LAST_WORDS.contains(&word.as_str()) {
+        orders[k] = StopOrder::Last;
+      } else if THEN_WORDS.contains(&word.as_str()) && k > 0 {
+        orders[k] = StopOrder::After { place_type: place_type(k - 1) };
+      }
+    }
+  }
+  orders
+}
+
+/// Rule-based counterpart of the model's analysis, producing the same [`PromptAnalyses`].
+///
+/// A category is requested when one of its keywords appears: its label, plural label or
+/// [`crate::catalog::CategoryDef::keywords`]. Only when no keyword appears at all, a multi-word
+/// tag found in a single pool requests that category instead, so `معماری قاجاری` alone still
+/// finds historical places. Infos follow prompt order, and a number before a category's keyword
+/// (`دو موزه`) becomes its `count`.
+///
+/// A starting point (see [`find_start`]) becomes [`PromptAnalyses::start`], and its words are not
+/// read as requests, so `از موزه سینما شروع کنیم` asks for no museum. Price, diet, wheelchair and
+/// time words (see [`take_constraints`]) become [`PromptAnalyses::constraints`], and ordering
+/// words (see [`read_orders`]) each info's [`PlaceInfo::order`].
+pub fn analyze_offline(catalog: &PlaceCatalog, prompt: &str) -> PromptAnalyses {
+  let mut tokens = split_words(prompt);
+  let constraints = take_constraints(&mut tokens);
+  let start = find_start(catalog, &tokens).map(|(span, anchor)| {
+    tokens[span].iter_mut().for_each(String::clear);
+    anchor.name.clone()
+  });
+  let mut detected = Vec::<Option<Detected>>::new();
+
+  for category in &catalog.categories {
+    let def = &category.def;
+    let keywords = [def.place_type.as_str(), def.label_fa_plural.as_str()]
+      .into_iter()
+      .chain(def.keywords.iter().map(String::as_str));
+    let mut found = None::<Detected>;
+    for keyword in keywords {
+      for span in find_phrase(&tokens, &split_words(keyword), KEYWORD_SUFFIXES) {
+        let count = count_before(&tokens, span.start);
+        let entry = found.get_or_insert(Detected {
+          first: span.start,
+          keywords_at: vec![],
+          count: None,
+          tags: vec![],
+        });
+        entry.first = entry.first.min(span.start);
+        entry.keywords_at.push(span.start);
+        entry.count = entry.count.max(count);
+      }
+    }
+    detected.push(found);
+  }
+
+  let any_keyword = detected.iter().any(Option::is_some);
+  let pools = catalog
+    .categories
+    .iter()
+    .map(|f| f.data.tag_pool.iter().map(|tag| normalize(tag)).collect::<HashSet<_>>())
+    .collect::<Vec<_>>();
+  for (index, category) in catalog.categories.iter().enumerate() {
+    let mut seen = HashSet::new();
+    for tag in &category.data.tag_pool {
+      let words = split_words(tag);
+      let key = words.join(" ");
+      if key.chars().count() < 2 || !seen.insert(key.clone()) {
+        continue;
+      }
+      let spans = find_phrase(&tokens, &words, TAG_SUFFIXES);
+      let Some(first) = spans.first().map(|f| f.start) else {
+        continue;
+      };
+      match &mut detected[index] {
+        Some(entry) => entry.tags.push(tag.clone()),
+        None if !any_keyword => {
+          let distinctive = words.len() > 1
+            && pools.iter().enumerate().all(|(i, pool)| i == index || !pool.contains(&key));
+          if distinctive {
+            detected[index] =
+              Some(Detected { first, keywords_at: vec![], count: None, tags: vec![tag.clone()] });
+          }
+        }
+        None => {}
+      }
+    }
+  }
+
+  let mut detected =
+    detected.into_iter().enumerate().filter_map(|(i, f)| Some((i, f?))).collect::<Vec<_>>();
+  detected.sort_by_key(|(_, f)| f.first);
+
+  let orders = read_orders(catalog, &tokens, &detected);
+  let place_infos = detected
+    .iter()
+    .zip(orders)
+    .map(|((index, found), order)| PlaceInfo {
+      place_type: catalog.categories[*index].def.place_type.clone(),
+      tags: found.tags.clone(),
+      count: found.count,
+      order,
+    })
+    .collect::<Vec<_>>();
+
+  let total_count = (0..tokens.len())
+    .filter(|&i| STOP_NOUNS.contains(&tokens[i].as_str()))
+    .find_map(|i| count_before(&tokens, i));
+
+  PromptAnalyses { place_infos, total_count, start, constraints }
+}
+
+/// Rule-based counterpart of the model's follow-up reading.
+///
+/// The place types and tags of `follow_up` are found as in [`analyze_offline`]. With a removing
+/// verb each of them drops its last stop; with a replacing verb the first one names the stop and
+/// the second, if any, what it becomes (`به جای موزه یه رستوران`); otherwise they are added.
+pub fn refine_offline(
+  catalog: &PlaceCatalog,
+  stops: &[PlanStop],
+  follow_up: &str,
+) -> Vec<PlanEdit> {
+  let tokens = split_words(follow_up);
+  let says = |words: &[&str]| {
+    words.iter().any(|word| !find_phrase(&tokens, &split_words(word), VERB_SUFFIXES).is_empty())
+  };
+  let infos = analyze_offline(catalog, follow_up).place_infos;
+  if says(REMOVE_WORDS) {
+    return infos
+      .into_iter()
+      .flat_map(|info| {
+        let remove = PlanEdit::Remove { stop: None, place_type: info.place_type };
+        std::iter::repeat_n(remove, info.count.unwrap_or(1) as usize)
+      })
+      .collect();
+  }
+  if says(REPLACE_WORDS) {
+    return match infos.as_slice() {
+      [] => vec![],
+      [info] => vec![PlanEdit::Replace { stop: None, info: info.clone() }],
+      [target, info, ..] => {
+        let stop =
+          stops.iter().rposition(|f| f.info.place_type == target.place_type).map(|i| i + 1);
+        vec![PlanEdit::Replace { stop, info: info.clone() }]
+      }
+    };
+  }
+  infos.into_iter().map(|info| PlanEdit::Add { info }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{catalog, place_type, MUSEUM, RESTAURANT};
+
+  fn counts(prompt: &str) -> Vec<(String, Option<u32>)> {
+    let analyses = analyze_offline(&catalog(), prompt);
+    analyses.place_infos.into_iter().map(|f| (f.place_type.as_str().to_string(), f.count)).collect()
+  }
+
+  fn count_of(prompt: &str) -> Option<u32> {
+    counts(prompt)[0].1
+  }
+
+  #[test]
+  fn reads_counts_per_type() {
+    assert_eq!(
+      counts("دو موزه و یک رستوران"),
+      [(MUSEUM.to_string(), Some(2)), (RESTAURANT.to_string(), Some(1))]
+    );
+    assert_eq!(count_of("سه تا موزه"), Some(3));
+    assert_eq!(count_of("۲ موزه"), Some(2));
+    assert_eq!(count_of("موزه ها"), None);
+    assert_eq!(count_of("۵۰ تا موزه"), None);
+  }
+
+  #[test]
+  fn reads_total_count() {
+    let analyses = analyze_offline(&catalog(), "چهار جا بریم، موزه و رستوران");
+    assert_eq!(analyses.total_count, Some(4));
+    assert!(analyses.place_infos.iter().all(|f| f.count.is_none()));
+  }
+
+  #[test]
+  fn nine_and_ten_need_a_classifier() {
+    assert_eq!(count_of("نه، موزه نمیخوام"), None);
+    assert_eq!(count_of("ده موزه"), None);
+    assert_eq!(count_of("نه تا رستوران"), Some(9));
+    assert_eq!(count_of("ده تا موزه"), Some(10));
+  }
+
+  #[test]
+  fn reads_time_budgets() {
+    let budget = |prompt: &str| analyze_offline(&catalog(), prompt).constraints.time_budget_minutes;
+    assert_eq!(budget("سه ساعت وقت دارم، موزه"), Some(180));
+    assert_eq!(budget("۲ ساعت و نیم موزه"), Some(150));
+    assert_eq!(budget("نیم ساعت"), Some(30));
+    assert_eq!(budget("۴۵ دقیقه"), Some(45));
+    assert_eq!(budget("یک موزه"), None);
+  }
+
+  #[test]
+  fn overlong_time_budget_is_dropped() {
+    for prompt in ["100000000 ساعت موزه", "4294967295 ساعت و نیم موزه", "99999999999 دقیقه موزه"]
+    {
+      let analyses = analyze_offline(&catalog(), prompt);
+      assert_eq!(analyses.constraints.time_budget_minutes, None, "{prompt}");
+      assert_eq!(analyses.place_infos.len(), 1, "{prompt}");
+    }
+  }
+
+  #[test]
+  fn zero_time_budget_is_dropped() {
+    for prompt in ["۰ ساعت وقت دارم، موزه", "0 دقیقه موزه"] {
+      let analyses = analyze_offline(&catalog(), prompt);
+      assert_eq!(analyses.constraints.time_budget_minutes, None, "{prompt}");
+      assert_eq!(analyses.place_infos.len(), 1, "{prompt}");
+    }
+    let budget = analyze_offline(&catalog(), "۰ ساعت و نیم").constraints.time_budget_minutes;
+    assert_eq!(budget, Some(30));
+  }
+
+  #[test]
+  fn negation_removes_one_stop() {
+    let edits = refine_offline(&catalog(), &[], "نه، موزه نمیخوام");
+    assert_eq!(edits, [PlanEdit::Remove { stop: None, place_type: place_type(MUSEUM) }]);
+    let edits = refine_offline(&catalog(), &[], "دو تا رستوران حذف کن");
+    assert_eq!(edits, vec![PlanEdit::Remove { stop: None, place_type: place_type(RESTAURANT) }; 2]);
+  }
+}
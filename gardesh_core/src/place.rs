@@ -0,0 +1,148 @@
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
+
+use crate::PlaceAttributes;
+
+/// Category of a place, as written in `Place.type` and in the LLM's `place_type`.
+///
+/// Holds the Persian label of an entry in the catalog's category registry (see
+/// [`crate::catalog::CategoryDef`]), so adding a category is a data change only.
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Default,
+  PartialEq,
+  Eq,
+  Hash,
+  PartialOrd,
+  Ord,
+  Clone,
+  derive_more::Display,
+)]
+#[serde(transparent)]
+pub struct PlaceType(String);
+
+impl PlaceType {
+  pub fn new(label: impl Into<String>) -> Self {
+    Self(label.into())
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Location {
+  pub x: f64,
+  pub y: f64,
+}
+
+/// Stable identity of a place, stored in the catalog next to each item.
+///
+/// Neshan search results carry no id of their own, so it is derived from the title and the
+/// coordinates; two branches of a chain share a title but never a location. Once written to the
+/// catalog the stored value is authoritative, so changing [`PlaceId::derive`] never renames
+/// existing places.
+#[derive(
+  Debug,
+  Serialize,
+  Deserialize,
+  Clone,
+  Default,
+  PartialEq,
+  Eq,
+  Hash,
+  PartialOrd,
+  Ord,
+  derive_more::Display,
+  derive_more::From,
+)]
+#[serde(transparent)]
+pub struct PlaceId(String);
+
+impl PlaceId {
+  pub fn derive(title: &str, location: &Location) -> Self {
+    let title = title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    // ~1 m of precision, so re-geocoding noise in the last digits does not change the id.
+    let key = format!("{title}|{:.5}|{:.5}", location.x, location.y);
+    Self(format!("p{:016x}", fnv1a(key.as_bytes())))
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.0
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+/// 64-bit FNV-1a; `DefaultHasher` is not guaranteed to be stable across Rust releases.
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+  bytes
+    .iter()
+    .fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Place {
+  #[serde(default)]
+  pub id: PlaceId,
+  pub title: String,
+  #[serde(default, skip_serializing_if = "String::is_empty")]
+  pub address: String,
+  pub category: String,
+  pub r#type: PlaceType,
+  pub region: String,
+  #[serde(default)]
+  pub neighbourhood: String,
+  pub location: Location,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  /// Price, diets and accessibility, where known; checked against a prompt's constraints.
+  #[serde(default, skip_serializing_if = "PlaceAttributes::is_empty")]
+  pub attributes: PlaceAttributes,
+}
+
+impl Place {
+  /// Fills in the id of items written before ids were stored in the catalog.
+  pub fn ensure_id(&mut self) {
+    if self.id.is_empty() {
+      self.id = PlaceId::derive(&self.title, &self.location);
+    }
+  }
+}
+
+impl Eq for Place {}
+
+impl Hash for Place {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.id.hash(state);
+  }
+}
+
+impl PartialEq for Place {
+  fn eq(&self, other: &Self) -> bool {
+    self.id == other.id
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct NeshanDataModel {
+  pub tag_pool: Vec<String>,
+  pub items: Vec<Place>,
+}
+
+impl NeshanDataModel {
+  pub fn ensure_ids(&mut self) {
+    self.items.iter_mut().for_each(Place::ensure_id);
+  }
+}
+
+#[derive(Hash, PartialEq, Eq, Debug, Serialize, Deserialize, Clone)]
+pub struct PlaceScoring {
+  pub place: Place,
+  pub score: usize,
+}
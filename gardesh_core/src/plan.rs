@@ -0,0 +1,518 @@
+//! Turning a [`PromptAnalyses`] into concrete stops: candidate places per requested type, narrowed
+//! by the prompt's constraints, and the combination and visiting order that keep the loop through
+//! them short while honoring the order the prompt asks for.
+
+use derive_more::{Display, Error};
+use iter_tools::Itertools;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering::Equal, Reverse};
+use std::collections::HashSet;
+
+use crate::constraints::trip_minutes;
+use crate::gazetteer::Anchor;
+use crate::{
+  distance_haversine, Constraints, Location, Place, PlaceCatalog, PlaceInfo, PlaceScoring,
+  PromptAnalyses, UnmetConstraint,
+};
+
+/// Loop length at which the distance term of a route's cost reaches 1.
+const MAX_ROUTE_METERS: f64 = 20_000.0;
+
+/// Best-scoring candidates of each requested type that take part in the route search.
+pub(crate) const MAX_ROUTE_CANDIDATES: usize = 12;
+
+/// Upper bound on the routes compared; every combination of candidates is tried in every visiting
+/// order, so long plans consider fewer candidates per type.
+const MAX_ROUTES: f64 = 50_000.0;
+
+/// Most infos whose visiting orders are all compared (720 orders); longer plans keep the order of
+/// the analysis.
+const MAX_ORDERED_INFOS: usize = 6;
+
+/// Longest plan produced, whatever the prompt asks for.
+pub const MAX_STOPS: usize = 10;
+
+/// One stop of a plan. `candidates` is never empty and starts with the place picked for the
+/// route, followed by the alternatives by descending tag score.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PlanStop {
+  pub info: PlaceInfo,
+  pub candidates: Vec<Place>,
+}
+
+/// A plan with the constraints it could not honor.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CheckedPlan {
+  pub stops: Vec<PlanStop>,
+  /// Where the route was measured from, when the prompt named a start the gazetteer knows.
+  #[serde(default)]
+  pub start: Option<Anchor>,
+  #[serde(default)]
+  pub unmet: Vec<UnmetConstraint>,
+}
+
+/// Why a prompt produced no plan; serializable so the Tauri backend can hand it to the page.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Display, Error)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlanError {
+  #[display("catalog unavailable: {message}")]
+  Catalog { message: String },
+  #[display("prompt analysis failed: {message}")]
+  Analyze { message: String },
+  #[display("no place type recognized in the prompt")]
+  NothingRequested,
+  #[display("no catalog place matches the requested types and tags")]
+  NoMatch,
+}
+
+/// Places of the info's type that match it, by descending tag score.
+pub(crate) fn candidates(catalog: &PlaceCatalog, info: &PlaceInfo) -> Vec<PlaceScoring> {
+  let mut candidates = catalog
+    .places_of(&info.place_type)
+    .map(|place| PlaceScoring { score: info.match_score(place), place: place.clone() })
+    // An info without tags asks for any place of its type.
+    .filter(|place_scoring| place_scoring.score > 0 || info.tags.is_empty())
+    .collect::<Vec<_>>();
+  candidates.sort_by_key(|f| Reverse(f.score));
+  candidates
+}
+
+/// The candidates meeting every constraint that applies to the info's category. A constraint
+/// none of them is known to meet is not applied but reported in `unmet`, once per place type.
+fn constrain(
+  catalog: &PlaceCatalog,
+  info: &PlaceInfo,
+  mut candidates: Vec<PlaceScoring>,
+  constraints: &Constraints,
+  unmet: &mut Vec<UnmetConstraint>,
+) -> Vec<PlaceScoring> {
+  if candidates.is_empty() {
+    return candidates;
+  }
+  let applicable = catalog.category(&info.place_type).map_or(&[][..], |f| &f.def.constraints);
+  for kind in constraints.kinds().into_iter().filter(|f| applicable.contains(f)) {
+    let kept = candidates
+      .iter()
+      .filter(|f| f.place.attributes.meets(kind, constraints))
+      .cloned()
+      .collect_vec();
+    if kept.is_empty() {
+      let missed =
+        UnmetConstraint::Attribute { constraint: kind, place_type: info.place_type.clone() };
+      if !unmet.contains(&missed) {
+        unmet.push(missed);
+      }
+    } else {
+      candidates = kept;
+    }
+  }
+  candidates
+}
+
+/// Cost of visiting `route`: mostly its length, minus a bonus for tag matches. The route is a
+/// closed loop, or with a `start` a path from there through the stops in order.
+pub(crate) fn route_cost(route: &[&PlaceScoring], start: Option<&Location>) -> f64 {
+  let distance = match start {
+    None => (0..route.len())
+      .map(|i| {
+        distance_haversine(&route[i].place.location, &route[(i + 1) % route.len()].place.location)
+      })
+      .sum::<f64>(),
+    Some(start) => {
+      start_leg(route, start)
+        + route
+          .windows(2)
+          .map(|f| distance_haversine(&f[0].place.location, &f[1].place.location))
+          .sum::<f64>()
+    }
+  };
+  // Scores start at 1 (0 for stops that asked for no tags); three matching tags is treated as a
+  // perfect match.
+  let score = route.iter().map(|item| item.score.saturating_sub(1) as f64 / 3.0).sum::<f64>()
+    / route.len() as f64;
+  0.7 * (distance / MAX_ROUTE_METERS) - 0.3 * score
+}
+
+fn start_leg(route: &[&PlaceScoring], start: &Location) -> f64 {
+  route.first().map_or(0.0, |f| distance_haversine(start, &f.place.location))
+}
+
+/// Stops planned for each info: its `count`, then trimmed or padded so the sum is `total_count`
+/// (at most [`MAX_STOPS`]). Trimming takes from the info with the most stops, the later one on a
+/// tie, so every type keeps a stop as long as possible; padding goes round the infos in order.
+fn stop_counts(infos: &[&PlaceInfo], total_count: Option<u32>) -> Vec<usize> {
+  let mut counts =
+    infos.iter().map(|info| info.count.map_or(1, |count| count.max(1) as usize)).collect_vec();
+  let total = total_count.map_or(counts.iter().sum(), |f| f as usize).clamp(1, MAX_STOPS);
+  while counts.iter().sum::<usize>() > total {
+    let (largest, _) = counts.iter().enumerate().max_by_key(|(_, count)| **count).unwrap();
+    counts[largest] -= 1;
+  }
+  for i in (0..counts.len()).cycle().take(total - counts.iter().sum::<usize>()) {
+    counts[i] += 1;
+  }
+  counts
+}
+
+/// Number of ways to pick `k` of `n` candidates.
+fn combinations(n: usize, k: usize) -> f64 {
+  (0..k.min(n)).map(|i| (n - i) as f64 / (i + 1) as f64).product()
+}
+
+/// How many of each info's best candidates the route search considers: as many as
+/// [`MAX_ROUTE_CANDIDATES`] allows, cut down, widest first, until the combinations to try fit in
+/// `max_routes`. An info keeps at least as many candidates as it has stops.
+fn route_widths(available: &[usize], counts: &[usize], max_routes: f64) -> Vec<usize> {
+  let mut widths = available.iter().map(|f| (*f).min(MAX_ROUTE_CANDIDATES)).collect_vec();
+  let routes = |widths: &[usize]| {
+    widths.iter().zip(counts).map(|(n, k)| combinations(*n, *k)).product::<f64>()
+  };
+  while routes(&widths) > max_routes {
+    let Some((widest, _)) = (0..widths.len())
+      .filter(|&i| widths[i] > counts[i].max(1))
+      .map(|i| (i, widths[i]))
+      .max_by_key(|(_, width)| *width)
+    else {
+      break;
+    };
+    widths[widest] -= 1;
+  }
+  widths
+}
+
+/// Orders in which to visit `infos`, each with `counts` stops, as indices into it: all those
+/// breaking the fewest [`PlaceInfo::order`]s, so flexible infos move freely around fixed ones and
+/// conflicting requests still leave some order. The analysis order comes first, so it wins ties.
+fn visit_orders(infos: &[&PlaceInfo], counts: &[usize]) -> Vec<Vec<usize>> {
+  if infos.len() > MAX_ORDERED_INFOS {
+    return vec![(0..infos.len()).collect()];
+  }
+  let broken = |order: &[usize]| {
+    let sequence = order
+      .iter()
+      .flat_map(|&i| std::iter::repeat_n(&infos[i].place_type, counts[i]))
+      .collect_vec();
+    let mut start = 0;
+    let spans = order.iter().map(|&i| {
+      start += counts[i];
+      (i, start - counts[i]..start)
+    });
+    spans.filter(|(i, stops)| !infos[*i].order.holds(stops.clone(), &sequence)).count()
+  };
+  let orders = (0..infos.len()).permutations(infos.len()).map(|f| (broken(&f), f)).collect_vec();
+  let fewest = orders.iter().map(|f| f.0).min().unwrap_or_default();
+  orders.into_iter().filter(|f| f.0 == fewest).map(|f| f.1).collect()
+}
+
+/// Picks places for every [`PlaceInfo`] of the analysis, as many distinct ones as its count asks
+/// for, with the plan trimmed or padded to [`PromptAnalyses::total_count`] (see [`stop_counts`]).
+///
+/// With a [`PromptAnalyses::start`] the gazetteer knows, the route is measured from there, and
+/// among equally scored candidates the ones closer to it are tried first; an unknown start is
+/// ignored (and reported by [`plan_checked`]).
+///
+/// The stops of each info are visited together, and the infos in the order that keeps the route
+/// cheapest among those honoring their [`PlaceInfo::order`] (see [`visit_orders`]).
+///
+/// Infos that match no place are dropped so one unknown tag does not sink the whole plan; the
+/// plan fails only when nothing was requested or nothing matched at all.
+pub fn plan(catalog: &PlaceCatalog, analyses: &PromptAnalyses) -> Result<Vec<PlanStop>, PlanError> {
+  plan_checked(catalog, analyses).map(|f| f.stops)
+}
+
+/// [`plan`], honoring [`PromptAnalyses::constraints`] where the catalog allows.
+///
+/// Candidates are first narrowed to places known to meet the price, diet and wheelchair
+/// constraints that apply to their category; a constraint that would leave a type without any
+/// candidate is ignored for it. With a time budget, stops are cut from the end of the plan, one
+/// at a time, until visits and travel fit in it, but the first stop is always kept. Whatever was
+/// not honored, an unknown start included, is listed in [`CheckedPlan::unmet`].
+pub fn plan_checked(
+  catalog: &PlaceCatalog,
+  analyses: &PromptAnalyses,
+) -> Result<CheckedPlan, PlanError> {
+  if analyses.place_infos.is_empty() {
+    return Err(PlanError::NothingRequested);
+  }
+  let anchor = analyses.start.as_deref().and_then(|f| catalog.gazetteer().resolve(f));
+  let start = anchor.map(|f| &f.location);
+  let mut unmet = Vec::new();
+  if let (Some(name), None) = (&analyses.start, anchor) {
+    unmet.push(UnmetConstraint::UnknownStart { name: name.clone() });
+  }
+  let mut scored = analyses
+    .place_infos
+    .iter()
+    .map(|info| {
+      let candidates = candidates(catalog, info);
+      (info, constrain(catalog, info, candidates, &analyses.constraints, &mut unmet))
+    })
+    .filter(|(_, candidates)| !candidates.is_empty())
+    .collect::<Vec<_>>();
+  if scored.is_empty() {
+    return Err(PlanError::NoMatch);
+  }
+  if let Some(start) = start {
+    let distance = |f: &PlaceScoring| distance_haversine(start, &f.place.location);
+    for (_, candidates) in &mut scored {
+      candidates.sort_by(|a, b| b.score.cmp(&a.score).then(distance(a).total_cmp(&distance(b))));
+    }
+  }
+  let infos = scored.iter().map(|(info, _)| *info).collect_vec();
+  let counts = stop_counts(&infos, analyses.total_count);
+  let orders = visit_orders(&infos, &counts);
+  let widths = route_widths(
+    &scored.iter().map(|(_, candidates)| candidates.len()).collect_vec(),
+    &counts,
+    MAX_ROUTES / orders.len() as f64,
+  );
+
+  // Each info contributes a combination of distinct candidates, repeated in turn when it has
+  // fewer candidates than stops; trimmed infos contribute nothing. Every combination is laid out
+  // in each of the visiting orders.
+  let best_route = |distinct: bool| {
+    scored
+      .iter()
+      .zip(&counts)
+      .zip(&widths)
+      .map(|(((_, candidates), &count), &width)| {
+        candidates[..width]
+          .iter()
+          .combinations(count.min(width))
+          .map(move |picked| picked.iter().copied().cycle().take(count).collect_vec())
+      })
+      .multi_cartesian_product()
+      .filter(|groups| !distinct || groups.iter().flatten().map(|f| &f.place.id).all_unique())
+      .flat_map(|groups| {
+        orders.iter().map(move |order| {
+          (order, order.iter().flat_map(|&i| groups[i].iter().copied()).collect_vec())
+        })
+      })
+      .map(|(order, route)| (route_cost(&route, start), order, route))
+      .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Equal))
+      .map(|(_, order, route)| (order.clone(), route))
+  };
+  // Two stops of one type should be two places, unless too few places match.
+  let (order, route) = best_route(true).or_else(|| best_route(false)).unwrap_or_default();
+  let chosen = route.iter().map(|f| &f.place.id).collect::<HashSet<_>>();
+
+  let stops =
+    order.iter().flat_map(|&i| std::iter::repeat_n((scored[i].0, &scored[i].1), counts[i]));
+  let mut stops = stops
+    .zip(&route)
+    .map(|((info, candidates), picked)| {
+      let alternatives = candidates.iter().filter(|f| !chosen.contains(&f.place.id));
+      let candidates =
+        std::iter::once(&picked.place).chain(alternatives.map(|f| &f.place)).cloned().collect();
+      PlanStop { info: info.clone(), candidates }
+    })
+    .collect_vec();
+
+  // A model may write a budget of no time for a prompt that names none; it is not a constraint.
+  if let Some(budget) = analyses.constraints.time_budget_minutes.filter(|f| *f > 0) {
+    let minutes = |stops: &[PlanStop]| {
+      trip_minutes(catalog, &stops.iter().map(|f| &f.candidates[0]).collect_vec(), start)
+    };
+    let planned = minutes(&stops);
+    if planned > budget {
+      let before = stops.len();
+      while stops.len() > 1 && minutes(&stops) > budget {
+        stops.pop();
+      }
+      unmet.push(UnmetConstraint::TimeBudget {
+        budget_minutes: budget,
+        planned_minutes: planned,
+        dropped: before - stops.len(),
+      });
+    }
+  }
+  Ok(CheckedPlan { stops, start: anchor.cloned(), unmet })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::constraints::{ConstraintKind, Diet, PriceLevel};
+  use crate::testing::{catalog, place_type, HISTORY, MUSEUM, RESTAURANT};
+  use crate::StopOrder;
+
+  fn info(label: &str, count: Option<u32>) -> PlaceInfo {
+    PlaceInfo { place_type: place_type(label), tags: vec![], count, order: StopOrder::Flexible }
+  }
+
+  fn types(stops: &[PlanStop]) -> Vec<&str> {
+    stops.iter().map(|f| f.info.place_type.as_str()).collect()
+  }
+
+  #[test]
+  fn stop_counts_clamp_and_trim_the_largest() {
+    let (a, b, c) = (info(MUSEUM, Some(0)), info(HISTORY, Some(3)), info(RESTAURANT, None));
+    assert_eq!(stop_counts(&[&a, &b, &c], None), [1, 3, 1]);
+    assert_eq!(stop_counts(&[&a, &b, &c], Some(0)), [1, 0, 0]);
+    assert_eq!(stop_counts(&[&a, &b, &c], Some(3)), [1, 1, 1]);
+    // Trimming takes from the later of equally large infos.
+    assert_eq!(stop_counts(&[&a, &b, &c], Some(2)), [1, 1, 0]);
+    // Padding goes round the infos in order.
+    assert_eq!(stop_counts(&[&a, &b, &c], Some(7)), [2, 4, 1]);
+    assert_eq!(stop_counts(&[&a, &b, &c], Some(100)).iter().sum::<usize>(), MAX_STOPS);
+    let many = info(MUSEUM, Some(40));
+    assert_eq!(stop_counts(&[&many], None), [MAX_STOPS]);
+  }
+
+  #[test]
+  fn trimmed_infos_get_no_stops() {
+    let analyses = PromptAnalyses {
+      place_infos: vec![info(MUSEUM, Some(2)), info(RESTAURANT, None), info(HISTORY, None)],
+      total_count: Some(2),
+      ..Default::default()
+    };
+    let stops = plan(&catalog(), &analyses).unwrap();
+    assert_eq!(stops.len(), 2);
+    assert!(!types(&stops).contains(&HISTORY));
+  }
+
+  #[test]
+  fn stops_of_one_type_are_distinct_places() {
+    let analyses =
+      PromptAnalyses { place_infos: vec![info(MUSEUM, Some(3))], ..Default::default() };
+    let stops = plan(&catalog(), &analyses).unwrap();
+    let ids = stops.iter().map(|f| &f.candidates[0].id).collect_vec();
+    assert_eq!(ids.len(), 3);
+    assert!(ids.iter().all_unique());
+  }
+
+  #[test]
+  fn time_budget_cuts_from_the_end() {
+    let mut restaurant = info(RESTAURANT, None);
+    restaurant.order = StopOrder::Last;
+    let mut analyses =
+      PromptAnalyses { place_infos: vec![restaurant, info(MUSEUM, Some(2))], ..Default::default() };
+    let full = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(types(&full.stops), [MUSEUM, MUSEUM, RESTAURANT]);
+
+    // Two museum visits take 180 minutes before any travel.
+    analyses.constraints.time_budget_minutes = Some(200);
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(types(&plan.stops), [MUSEUM, MUSEUM]);
+    let [UnmetConstraint::TimeBudget { budget_minutes: 200, planned_minutes, dropped: 1 }] =
+      plan.unmet[..]
+    else {
+      panic!("unexpected unmet constraints: {:?}", plan.unmet);
+    };
+    assert!(planned_minutes >= 90 + 90 + 75);
+
+    // The first stop stays however short the budget.
+    analyses.constraints.time_budget_minutes = Some(10);
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(types(&plan.stops), [MUSEUM]);
+    assert!(matches!(plan.unmet[..], [UnmetConstraint::TimeBudget { dropped: 2, .. }]));
+
+    analyses.constraints.time_budget_minutes = Some(0);
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(plan.stops.len(), 3);
+    assert!(plan.unmet.is_empty());
+
+    analyses.constraints.time_budget_minutes = Some(24 * 60);
+    assert!(plan_checked(&catalog(), &analyses).unwrap().unmet.is_empty());
+  }
+
+  #[test]
+  fn positions_count_stops() {
+    let mut restaurant = info(RESTAURANT, None);
+    restaurant.order = StopOrder::Position { position: 3 };
+    let analyses = PromptAnalyses {
+      place_infos: vec![info(HISTORY, None), restaurant.clone(), info(MUSEUM, Some(2))],
+      ..Default::default()
+    };
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(types(&plan.stops)[2], RESTAURANT);
+
+    // A trimmed info neither takes a position nor breaks its own order.
+    let mut first = info(HISTORY, None);
+    first.order = StopOrder::Position { position: 1 };
+    restaurant.order = StopOrder::Position { position: 1 };
+    let analyses = PromptAnalyses {
+      place_infos: vec![restaurant, info(MUSEUM, Some(2)), first],
+      total_count: Some(2),
+      ..Default::default()
+    };
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(types(&plan.stops), [RESTAURANT, MUSEUM]);
+  }
+
+  #[test]
+  fn constraints_narrow_candidates() {
+    let mut analyses = PromptAnalyses {
+      place_infos: vec![info(RESTAURANT, None), info(MUSEUM, None)],
+      ..Default::default()
+    };
+    analyses.constraints.diets = vec![Diet::Vegan];
+    analyses.constraints.wheelchair = true;
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    let picked = |label: &str| {
+      let stop = plan.stops.iter().find(|f| f.info.place_type.as_str() == label).unwrap();
+      stop.candidates.iter().map(|f| f.title.as_str()).collect_vec()
+    };
+    // Only the café is vegan; the diet does not apply to museums.
+    assert_eq!(picked(MUSEUM), ["موزه ملی ایران"]);
+    assert_eq!(picked(RESTAURANT), ["کافه باغ"]);
+    assert_eq!(
+      plan.unmet,
+      [UnmetConstraint::Attribute {
+        constraint: ConstraintKind::Wheelchair,
+        place_type: place_type(RESTAURANT)
+      }]
+    );
+  }
+
+  #[test]
+  fn unmet_attributes_are_reported_once_per_type() {
+    let mut analyses = PromptAnalyses {
+      place_infos: vec![info(RESTAURANT, None), info(RESTAURANT, None)],
+      ..Default::default()
+    };
+    analyses.constraints.diets = vec![Diet::GlutenFree];
+    analyses.constraints.max_price = Some(PriceLevel::Cheap);
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(plan.stops.len(), 2);
+    assert!(plan
+      .stops
+      .iter()
+      .all(|f| f.candidates[0].attributes.price_level == Some(PriceLevel::Cheap)));
+    assert_eq!(
+      plan.unmet,
+      [UnmetConstraint::Attribute {
+        constraint: ConstraintKind::Diet,
+        place_type: place_type(RESTAURANT)
+      }]
+    );
+  }
+
+  #[test]
+  fn start_is_returned_or_reported() {
+    let mut analyses = PromptAnalyses {
+      place_infos: vec![info(MUSEUM, None)],
+      start: Some("میدان انقلاب".to_string()),
+      ..Default::default()
+    };
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert_eq!(plan.start.map(|f| f.name).as_deref(), Some("میدان انقلاب"));
+    assert_eq!(plan.stops[0].candidates[0].title, "موزه فرش");
+    assert!(plan.unmet.is_empty());
+
+    analyses.start = Some("دربند".to_string());
+    let plan = plan_checked(&catalog(), &analyses).unwrap();
+    assert!(plan.start.is_none());
+    assert_eq!(plan.unmet, [UnmetConstraint::UnknownStart { name: "دربند".to_string() }]);
+  }
+
+  #[test]
+  fn nothing_requested_or_matched() {
+    let analyses = PromptAnalyses::default();
+    assert_eq!(plan(&catalog(), &analyses).unwrap_err(), PlanError::NothingRequested);
+    let mut unknown = info(MUSEUM, None);
+    unknown.tags = vec!["ورزش".to_string()];
+    let analyses = PromptAnalyses { place_infos: vec![unknown], ..Default::default() };
+    assert_eq!(plan(&catalog(), &analyses).unwrap_err(), PlanError::NoMatch);
+  }
+}
@@ -0,0 +1,241 @@
+//! Picking the tags worth showing the model. Listing every pool in the system prompt grows with
+//! each category and city, so only the tags closest to the prompt are kept from each pool; the
+//! rest of the pool is still accepted when the answer is [`crate::validate::repair`]ed.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{self, Display};
+
+use crate::prompt::system_prompt;
+use crate::validate::tag_distance;
+use crate::{normalize, PlaceCatalog, PlaceType};
+
+/// Tags kept from each pool by default.
+pub const DEFAULT_PROMPT_TAGS: usize = 40;
+
+/// Words closer than this count as the same word for [`LexicalSimilarity`], so `قاجاری` still
+/// finds `دوره قاجار`.
+const MAX_WORD_DISTANCE: f64 = 0.2;
+
+/// Words shorter than this (`و`, `با`, `یک`, …) are too common to relate a tag to a prompt.
+const MIN_WORD_CHARS: usize = 3;
+
+fn words(text: &str) -> Vec<&str> {
+  text.split_whitespace().filter(|f| f.chars().count() >= MIN_WORD_CHARS).collect()
+}
+
+/// How close a tag is to a prompt, from 0 (unrelated) to 1.
+pub trait TagSimilarity {
+  fn similarity(&self, text: &str, place_type: &PlaceType, tag: &str) -> f64;
+}
+
+/// Overlap of [`normalize`]d words: 1 when the tag appears in the text as is, otherwise the share
+/// of the tag's words that have a close word in the text. Very short words are left out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LexicalSimilarity;
+
+impl TagSimilarity for LexicalSimilarity {
+  fn similarity(&self, text: &str, _: &PlaceType, tag: &str) -> f64 {
+    let (text, tag) = (normalize(text), normalize(tag));
+    if tag.trim().is_empty() {
+      return 0.0;
+    }
+    if text.contains(tag.trim()) {
+      return 1.0;
+    }
+    let (words, tag_words) = (words(&text), words(&tag));
+    if tag_words.is_empty() {
+      return 0.0;
+    }
+    let close = tag_words
+      .iter()
+      .filter(|tag_word| words.iter().any(|word| tag_distance(tag_word, word) <= MAX_WORD_DISTANCE))
+      .count();
+    close as f64 / tag_words.len() as f64
+  }
+}
+
+/// The part of each tag pool shown to the model, in pool order.
+#[derive(Debug, Clone, Default)]
+pub struct TagSelection {
+  pub pools: BTreeMap<PlaceType, Vec<String>>,
+}
+
+impl TagSelection {
+  /// Every tag of every pool.
+  pub fn all(catalog: &PlaceCatalog) -> Self {
+    let pools = catalog
+      .categories
+      .iter()
+      .map(|f| (f.def.place_type.clone(), f.data.tag_pool.clone()))
+      .collect();
+    Self { pools }
+  }
+
+  /// Up to `limit` tags of each pool, the most similar to `text` first and, among equally
+  /// similar ones, those used by the most places. A tag listed more than once in a pool is kept
+  /// once. Without a limit every tag is kept.
+  pub fn select(
+    catalog: &PlaceCatalog,
+    text: &str,
+    limit: Option<usize>,
+    similarity: &impl TagSimilarity,
+  ) -> Self {
+    let Some(limit) = limit else {
+      return Self::all(catalog);
+    };
+    let mut pools = BTreeMap::new();
+    for category in &catalog.categories {
+      let place_type = &category.def.place_type;
+      let pool = &category.data.tag_pool;
+      let mut usage = HashMap::<String, usize>::new();
+      for tag in category.data.items.iter().flat_map(|f| &f.tags) {
+        *usage.entry(normalize(tag)).or_default() += 1;
+      }
+      let mut seen = HashSet::new();
+      let mut ranked = pool
+        .iter()
+        .enumerate()
+        .filter(|(_, tag)| seen.insert(normalize(tag)))
+        .map(|(i, tag)| {
+          let used = usage.get(&normalize(tag)).copied().unwrap_or(0);
+          (similarity.similarity(text, place_type, tag), used, i)
+        })
+        .collect::<Vec<_>>();
+      ranked.sort_by(|a, b| b.0.total_cmp(&a.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+      let mut kept = ranked.into_iter().take(limit).map(|f| f.2).collect::<Vec<_>>();
+      kept.sort();
+      pools.insert(place_type.clone(), kept.into_iter().map(|i| pool[i].clone()).collect());
+    }
+    Self { pools }
+  }
+
+  /// The selected part of `place_type`'s pool, or the whole pool when it was not selected from.
+  pub fn pool<'a>(&'a self, catalog: &'a PlaceCatalog, place_type: &PlaceType) -> &'a [String] {
+    match self.pools.get(place_type) {
+      Some(pool) => pool,
+      None => catalog.tag_pool(place_type),
+    }
+  }
+
+  pub fn tag_count(&self) -> usize {
+    self.pools.values().map(Vec::len).sum()
+  }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PromptSize {
+  pub tags: usize,
+  pub chars: usize,
+}
+
+/// Size of the system prompt with a [`TagSelection`] against the one listing every tag.
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PromptSavings {
+  pub full: PromptSize,
+  pub selected: PromptSize,
+}
+
+impl PromptSavings {
+  pub fn measure(catalog: &PlaceCatalog, selection: &TagSelection) -> Self {
+    let size = |selection: &TagSelection| PromptSize {
+      tags: selection.tag_count(),
+      chars: system_prompt(catalog, selection).chars().count(),
+    };
+    Self { full: size(&TagSelection::all(catalog)), selected: size(selection) }
+  }
+
+  /// Share of the full prompt's characters left out, from 0 to 1.
+  pub fn saved(&self) -> f64 {
+    match self.full.chars {
+      0 => 0.0,
+      full => 1.0 - self.selected.chars as f64 / full as f64,
+    }
+  }
+}
+
+impl Display for PromptSavings {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "system prompt: {} of {} tags, {} of {} chars ({:.0}% smaller)",
+      self.selected.tags,
+      self.full.tags,
+      self.selected.chars,
+      self.full.chars,
+      self.saved() * 100.0
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{catalog, place_type, MUSEUM};
+
+  /// The test catalog with the museum pool grown past [`DEFAULT_PROMPT_TAGS`]: the museums' own
+  /// tags, then fillers no place uses, then `خوشنویسی`, which no place uses either.
+  fn large_catalog() -> PlaceCatalog {
+    let mut catalog = catalog();
+    let museum = place_type(MUSEUM);
+    let category = catalog.categories.iter_mut().find(|f| f.def.place_type == museum).unwrap();
+    let pool = &mut category.data.tag_pool;
+    pool.extend((0..DEFAULT_PROMPT_TAGS).map(|i| format!("برچسب {i}")));
+    pool.push("خوشنویسی".to_string());
+    catalog
+  }
+
+  fn museum_tags(catalog: &PlaceCatalog, prompt: &str) -> Vec<String> {
+    let selection =
+      TagSelection::select(catalog, prompt, Some(DEFAULT_PROMPT_TAGS), &LexicalSimilarity);
+    selection.pools[&place_type(MUSEUM)].clone()
+  }
+
+  #[test]
+  fn mentioned_tags_are_kept() {
+    let catalog = large_catalog();
+    let tags = museum_tags(&catalog, "یک موزه خوشنویسی");
+    assert_eq!(tags.len(), DEFAULT_PROMPT_TAGS);
+    assert!(tags.contains(&"خوشنویسی".to_string()));
+    // Unmentioned, it loses to tags places use and then to earlier pool tags.
+    let tags = museum_tags(&catalog, "یک موزه");
+    assert_eq!(tags.len(), DEFAULT_PROMPT_TAGS);
+    assert!(!tags.contains(&"خوشنویسی".to_string()));
+    assert!(tags.contains(&"فرش".to_string()));
+  }
+
+  #[test]
+  fn no_limit_lists_whole_pools() {
+    let catalog = large_catalog();
+    let selection = TagSelection::select(&catalog, "یک موزه", None, &LexicalSimilarity);
+    for category in &catalog.categories {
+      assert_eq!(selection.pool(&catalog, &category.def.place_type), category.data.tag_pool);
+    }
+    assert_eq!(
+      selection.tag_count(),
+      catalog.categories.iter().map(|f| f.data.tag_pool.len()).sum::<usize>()
+    );
+  }
+
+  #[test]
+  fn savings_compare_with_the_full_prompt() {
+    let catalog = large_catalog();
+    let all = PromptSavings::measure(&catalog, &TagSelection::all(&catalog));
+    assert_eq!(all.full, all.selected);
+    assert_eq!(
+      all.full.chars,
+      system_prompt(&catalog, &TagSelection::all(&catalog)).chars().count()
+    );
+    assert_eq!(all.saved(), 0.0);
+
+    let selection =
+      TagSelection::select(&catalog, "یک موزه", Some(DEFAULT_PROMPT_TAGS), &LexicalSimilarity);
+    let savings = PromptSavings::measure(&catalog, &selection);
+    assert_eq!(savings.full, all.full);
+    assert_eq!(savings.selected.tags, selection.tag_count());
+    // Only the museum pool is longer than the limit.
+    assert_eq!(savings.full.tags - savings.selected.tags, 6);
+    assert!(savings.selected.chars < savings.full.chars);
+    assert!(savings.saved() > 0.0);
+  }
+}
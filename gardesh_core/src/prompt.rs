@@ -0,0 +1,191 @@
+use serde_json::{json, Value};
+
+use crate::prefilter::TagSelection;
+use crate::PlaceCatalog;
+
+/// System prompt for prompt analysis, listing every registered category and the selected part of
+/// its tag pool.
+pub fn system_prompt(catalog: &PlaceCatalog, selection: &TagSelection) -> String {
+  let labels = catalog.place_types().map(|f| f.as_str()).collect::<Vec<_>>();
+  let mut prompt = format!(
+    "
+    درخواست کاربر را تجزیه تحلیل کن.
+    هر نوع مکان ذکر شده را شناسایی کن که یکی از این {} نوع است: {}.
+    سپس برای هر مکان، از بین لیست تگ های زیر، مرتبط ترین موارد به درخواست کاربر را انتخاب کن.
+    اگر کاربر برای یک نوع مکان تعداد گفت (مثلا «دو موزه»)، آن را در متغیر count همان مکان بیاور.
+    اگر کاربر در درخواست خود تعداد مکان هایی که میخواهد ببیند را ذکر کرد، آن را هم در متغیر total_count بیاور.
+    اگر کاربر گفت کجاست یا از کجا شروع میکند (مثلا «من در دربند هستم» یا «از میدان ارگ شروع کنیم»)، نام آن مکان یا محله را در start بیاور و آن را جزو مکان ها نیاور.
+    اگر کاربر ترتیب بازدید را گفت (مثلا «اول یک خانه تاریخی، بعد ناهار، بعد موزه»)، مکان ها را به همان ترتیب بیاور و ترتیب هر مکان را در order بنویس: برای جایگاه ثابت kind را position بگذار و شماره جایگاه را در position بیاور، برای آخرین مکان last، برای «قبل از» یا «بعد از» یک نوع مکان before یا after همراه با آن نوع در place_type، و اگر ترتیبی نگفت order را نیاور.
+    محدودیت های کاربر را در constraints بیاور: وقتی که دارد را به دقیقه در time_budget_minutes (مثلا «سه ساعت وقت دارم» یعنی 180)، گران ترین قیمتی که میخواهد را در max_price (cheap برای «ارزان»، moderate برای «متوسط»)، رژیم غذایی را در diets (vegetarian برای گیاهی، vegan، gluten_free برای بدون گلوتن) و اگر با ویلچر است wheelchair را true بگذار.\n",
+    labels.len(),
+    labels.join("، "),
+  );
+  for category in &catalog.categories {
+    prompt += &format!(
+      "    لیست تگ های {}:[\n{}]\n\n",
+      category.def.label_fa_plural,
+      selection.pool(catalog, &category.def.place_type).join("\n"),
+    );
+  }
+  prompt
+}
+
+/// JSON schema of [`crate::PromptAnalyses`] passed to the model as its response format.
+pub fn response_schema(catalog: &PlaceCatalog) -> Value {
+  json!(
+    {
+      "type": "object",
+      "properties": {
+        "place_infos": {
+          "type": "array",
+          "items": {
+            "type": "object",
+            "properties": {
+              "tags": {
+                "type": "array",
+                "items": {
+                  "type": "string"
+                }
+              },
+              "place_type": {
+                "type": "string",
+                "enum": catalog.place_types().collect::<Vec<_>>()
+              },
+              "count": {
+                "type": "integer"
+              },
+              "order": {
+                "type": "object",
+                "properties": {
+                  "kind": {
+                    "type": "string",
+                    "enum": ["flexible", "position", "last", "before", "after"]
+                  },
+                  "position": {
+                    "type": "integer"
+                  },
+                  "place_type": {
+                    "type": "string",
+                    "enum": catalog.place_types().collect::<Vec<_>>()
+                  }
+                },
+                "required": [
+                  "kind"
+                ]
+              }
+            }
+          }
+        },
+        "total_count": {
+          "type": "integer"
+        },
+        "start": {
+          "type": "string"
+        },
+        "constraints": {
+          "type": "object",
+          "properties": {
+            "time_budget_minutes": {
+              "type": "integer"
+            },
+            "max_price": {
+              "type": "string",
+              "enum": ["cheap", "moderate", "expensive"]
+            },
+            "diets": {
+              "type": "array",
+              "items": {
+                "type": "string",
+                "enum": ["vegetarian", "vegan", "gluten_free"]
+              }
+            },
+            "wheelchair": {
+              "type": "boolean"
+            }
+          }
+        }
+      },
+      "required": [
+        "place_infos"
+      ]
+    }
+  )
+}
+
+/// System prompt for follow-ups: the same categories and tag pools as [`system_prompt`], with the
+/// current plan expected in the last user message.
+pub fn refine_system_prompt(catalog: &PlaceCatalog, selection: &TagSelection) -> String {
+  let mut prompt = system_prompt(catalog, selection);
+  prompt += "
+    کاربر قبلا یک برنامه گرفته است و برنامه فعلی به صورت لیست شماره دار در آخرین پیام او آمده است.
+    درخواست جدید را به صورت تغییر در همین برنامه بیان کن، نه یک برنامه تازه:
+    برای افزودن مکان op را add بگذار، برای عوض کردن یک مکان replace و برای حذف آن remove.
+    در replace و remove شماره مکان مورد نظر را در stop بیاور و در replace نوع و تگ های مکان جایگزین را بنویس.
+    اگر کاربر تعداد مکان های جدید را گفت، آن را در count بیاور و اگر گفت کجای برنامه باشند (مثلا «آخرش یه کافه»)، آن را در order بیاور.\n";
+  prompt
+}
+
+/// JSON schema of [`crate::conversation::PlanEdits`] passed to the model for follow-ups.
+pub fn edits_schema(catalog: &PlaceCatalog) -> Value {
+  json!(
+    {
+      "type": "object",
+      "properties": {
+        "edits": {
+          "type": "array",
+          "items": {
+            "type": "object",
+            "properties": {
+              "op": {
+                "type": "string",
+                "enum": ["add", "replace", "remove"]
+              },
+              "stop": {
+                "type": "integer"
+              },
+              "place_type": {
+                "type": "string",
+                "enum": catalog.place_types().collect::<Vec<_>>()
+              },
+              "tags": {
+                "type": "array",
+                "items": {
+                  "type": "string"
+                }
+              },
+              "count": {
+                "type": "integer"
+              },
+              "order": {
+                "type": "object",
+                "properties": {
+                  "kind": {
+                    "type": "string",
+                    "enum": ["flexible", "position", "last", "before", "after"]
+                  },
+                  "position": {
+                    "type": "integer"
+                  },
+                  "place_type": {
+                    "type": "string",
+                    "enum": catalog.place_types().collect::<Vec<_>>()
+                  }
+                },
+                "required": [
+                  "kind"
+                ]
+              }
+            },
+            "required": [
+              "op",
+              "place_type"
+            ]
+          }
+        }
+      },
+      "required": [
+        "edits"
+      ]
+    }
+  )
+}
@@ -0,0 +1,268 @@
+//! Consistency checks over a loaded catalog, run by the data team before shipping a pack.
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{self, Display};
+
+use crate::{normalize, PlaceCatalog, PlaceId};
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+  Error,
+  Warning,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueKind {
+  /// A `tag_pool` entry no item of the category uses.
+  OrphanTag,
+  /// An item tag missing from its category's `tag_pool`, so the model can never ask for it.
+  UnpooledTag,
+  /// An item without tags; it can never match a prompt.
+  UntaggedItem,
+  DuplicateId,
+  DuplicateTitle,
+  OutsideRegion,
+  /// The same tag listed more than once in a `tag_pool`.
+  DuplicateTag,
+  /// Pool tags that only differ by whitespace or zero-width non-joiners.
+  NearDuplicateTag,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Issue {
+  pub severity: Severity,
+  pub kind: IssueKind,
+  /// Category file the issue was found in.
+  pub file: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub place: Option<PlaceId>,
+  pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct QualityReport {
+  pub version: String,
+  pub issues: Vec<Issue>,
+}
+
+fn tag_key(tag: &str) -> String {
+  normalize(tag).replace(' ', "")
+}
+
+impl QualityReport {
+  pub fn check(catalog: &PlaceCatalog) -> Self {
+    let mut issues = Vec::new();
+    let mut seen_ids = HashSet::new();
+    for category in &catalog.categories {
+      let file = &category.def.file;
+      let mut issue = |severity, kind, place: Option<&PlaceId>, message: String| {
+        issues.push(Issue { severity, kind, file: file.clone(), place: place.cloned(), message })
+      };
+      let pool = &category.data.tag_pool;
+      let items = &category.data.items;
+
+      let used = items.iter().flat_map(|f| &f.tags).collect::<HashSet<_>>();
+      for tag in pool.iter().filter(|tag| !used.contains(tag)) {
+        issue(
+          Severity::Warning,
+          IssueKind::OrphanTag,
+          None,
+          format!("tag `{tag}` is used by no item"),
+        );
+      }
+
+      let mut by_key = BTreeMap::<String, Vec<&String>>::new();
+      for tag in pool {
+        let variants = by_key.entry(tag_key(tag)).or_default();
+        if variants.contains(&tag) {
+          issue(
+            Severity::Warning,
+            IssueKind::DuplicateTag,
+            None,
+            format!("tag `{tag}` is listed twice"),
+          );
+        } else {
+          variants.push(tag);
+        }
+      }
+      for variants in by_key.values().filter(|f| f.len() > 1) {
+        let variants = variants.iter().map(|f| format!("`{f}`")).collect::<Vec<_>>();
+        issue(
+          Severity::Warning,
+          IssueKind::NearDuplicateTag,
+          None,
+          format!("tags {} differ only by spacing", variants.join(", ")),
+        );
+      }
+
+      let mut titles = BTreeMap::<String, Vec<&PlaceId>>::new();
+      for place in items {
+        let id = Some(&place.id);
+        if !seen_ids.insert(&place.id) {
+          issue(
+            Severity::Error,
+            IssueKind::DuplicateId,
+            id,
+            format!("`{}` reuses id {}", place.title, place.id),
+          );
+        }
+        if place.tags.is_empty() {
+          issue(
+            Severity::Error,
+            IssueKind::UntaggedItem,
+            id,
+            format!("`{}` has no tags", place.title),
+          );
+        }
+        for tag in place.tags.iter().filter(|tag| !pool.contains(tag)) {
+          issue(
+            Severity::Warning,
+            IssueKind::UnpooledTag,
+            id,
+            format!("`{}` uses tag `{tag}` missing from the tag pool", place.title),
+          );
+        }
+        if let Some(region) = catalog.region.as_ref().filter(|f| !f.contains(&place.location)) {
+          issue(
+            Severity::Error,
+            IssueKind::OutsideRegion,
+            id,
+            format!(
+              "`{}` at {}, {} is outside {}",
+              place.title, place.location.x, place.location.y, region.name
+            ),
+          );
+        }
+        titles.entry(normalize(&place.title)).or_default().push(&place.id);
+      }
+      for ids in titles.values().filter(|f| f.len() > 1) {
+        let title =
+          items.iter().find(|f| f.id == *ids[0]).map(|f| f.title.as_str()).unwrap_or_default();
+        // Branches of a chain legitimately share a title, so this is only worth a look.
+        issue(
+          Severity::Warning,
+          IssueKind::DuplicateTitle,
+          Some(ids[0]),
+          format!("`{title}` appears {} times", ids.len()),
+        );
+      }
+    }
+    issues.sort_by(|a, b| (a.severity, &a.file).cmp(&(b.severity, &b.file)));
+    Self { version: catalog.version.clone(), issues }
+  }
+
+  pub fn count(&self, severity: Severity) -> usize {
+    self.issues.iter().filter(|f| f.severity == severity).count()
+  }
+
+  pub fn has_errors(&self) -> bool {
+    self.count(Severity::Error) > 0
+  }
+}
+
+impl Display for QualityReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    writeln!(f, "Catalog {}", self.version)?;
+    for issue in &self.issues {
+      let severity = match issue.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+      };
+      writeln!(f, "{severity}: {}: {}", issue.file, issue.message)?;
+    }
+    write!(f, "{} errors, {} warnings", self.count(Severity::Error), self.count(Severity::Warning))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::catalog::Region;
+  use crate::testing::catalog;
+  use crate::{Location, NeshanDataModel};
+
+  /// The kinds of issue found after `change` is applied to the first category's data.
+  fn issues_after(change: impl FnOnce(&mut NeshanDataModel)) -> Vec<(Severity, IssueKind)> {
+    let mut catalog = catalog();
+    change(&mut catalog.categories[0].data);
+    QualityReport::check(&catalog).issues.iter().map(|f| (f.severity, f.kind)).collect()
+  }
+
+  #[test]
+  fn test_catalog_is_clean() {
+    assert_eq!(issues_after(|_| {}), []);
+  }
+
+  #[test]
+  fn pool_tags_used_by_no_item_are_orphans() {
+    let found = issues_after(|data| data.tag_pool.push("باغ".to_string()));
+    assert_eq!(found, [(Severity::Warning, IssueKind::OrphanTag)]);
+  }
+
+  #[test]
+  fn item_tags_missing_from_the_pool_are_unpooled() {
+    let found = issues_after(|data| data.tag_pool.retain(|f| f != "معماری مدرن"));
+    assert_eq!(found, [(Severity::Warning, IssueKind::UnpooledTag)]);
+  }
+
+  #[test]
+  fn items_without_tags_are_errors() {
+    let found = issues_after(|data| data.items[2].tags.clear());
+    // `معماری مدرن` was the item's only tag, so it is orphaned as well.
+    assert_eq!(
+      found,
+      [(Severity::Error, IssueKind::UntaggedItem), (Severity::Warning, IssueKind::OrphanTag)]
+    );
+  }
+
+  #[test]
+  fn reused_ids_are_errors_and_shared_titles_warnings() {
+    let found = issues_after(|data| {
+      let mut copy = data.items[0].clone();
+      copy.location.x += 0.01;
+      data.items.push(copy);
+    });
+    assert_eq!(
+      found,
+      [(Severity::Error, IssueKind::DuplicateId), (Severity::Warning, IssueKind::DuplicateTitle)]
+    );
+  }
+
+  #[test]
+  fn places_outside_the_region_are_errors() {
+    let mut catalog = catalog();
+    catalog.region = Some(Region {
+      name: "تهران".to_string(),
+      south_west: Location { x: 51.2, y: 35.6 },
+      north_east: Location { x: 51.6, y: 35.85 },
+    });
+    assert!(!QualityReport::check(&catalog).has_errors());
+    catalog.categories[1].data.items[0].location = Location { x: 51.67, y: 32.65 };
+    let report = QualityReport::check(&catalog);
+    assert_eq!(
+      report.issues.iter().map(|f| f.kind).collect::<Vec<_>>(),
+      [IssueKind::OutsideRegion]
+    );
+    assert!(report.has_errors());
+  }
+
+  #[test]
+  fn repeated_and_near_duplicate_pool_tags_are_warnings() {
+    let found = issues_after(|data| {
+      data.tag_pool.push("معماری".to_string());
+      data.tag_pool.push("معماری\u{200c}مدرن".to_string());
+    });
+    // No item uses the spelling with a zero-width non-joiner, so it is also an orphan.
+    assert_eq!(
+      found,
+      [
+        (Severity::Warning, IssueKind::OrphanTag),
+        (Severity::Warning, IssueKind::DuplicateTag),
+        (Severity::Warning, IssueKind::NearDuplicateTag)
+      ]
+    );
+  }
+}
@@ -0,0 +1,185 @@
+use rstar::primitives::GeomWithData;
+use rstar::{RTree, AABB};
+
+use crate::{distance_haversine, Location};
+
+const EARTH_RADIUS: f64 = 6371e3; // meters
+
+/// Slack on projected distances before the exact haversine check; the projection is only
+/// accurate near the reference latitude.
+const PROJECTION_SLACK: f64 = 1.05;
+
+type Entry = GeomWithData<[f64; 2], usize>;
+
+/// R-tree over place locations, answering queries with haversine distances in meters.
+///
+/// Points are stored in an equirectangular projection around the mean latitude, so the tree's
+/// Euclidean metric orders neighbours like haversine does at city scale; every result is
+/// re-checked with [`distance_haversine`]. Entries carry the position of the place in the slice
+/// the index was built from.
+#[derive(Debug, Clone, Default)]
+pub struct SpatialIndex {
+  tree: RTree<Entry>,
+  locations: Vec<Location>,
+  lat_cos: f64,
+}
+
+impl SpatialIndex {
+  pub fn build<'a>(locations: impl IntoIterator<Item = &'a Location>) -> Self {
+    let locations = locations.into_iter().cloned().collect::<Vec<_>>();
+    let mean_lat = locations.iter().map(|f| f.y).sum::<f64>() / locations.len().max(1) as f64;
+    let mut index = Self { tree: RTree::new(), locations, lat_cos: mean_lat.to_radians().cos() };
+    let entries = index
+      .locations
+      .iter()
+      .enumerate()
+      .map(|(i, location)| Entry::new(index.project(location), i))
+      .collect();
+    index.tree = RTree::bulk_load(entries);
+    index
+  }
+
+  fn project(&self, location: &Location) -> [f64; 2] {
+    [location.x.to_radians() * EARTH_RADIUS * self.lat_cos, location.y.to_radians() * EARTH_RADIUS]
+  }
+
+  /// The `n` closest entries accepted by `filter`, nearest first.
+  pub fn nearest(
+    &self,
+    origin: &Location,
+    n: usize,
+    filter: impl Fn(usize) -> bool,
+  ) -> Vec<(usize, f64)> {
+    let mut found = Vec::<(usize, f64)>::new();
+    if n == 0 {
+      return found;
+    }
+    let candidates = self.tree.nearest_neighbor_iter_with_distance_2(&self.project(origin));
+    for (entry, projected_2) in candidates.filter(|(entry, _)| filter(entry.data)) {
+      // Stop once no remaining candidate can beat the n-th exact distance found so far.
+      if found.len() >= n && projected_2.sqrt() > found[n - 1].1 * PROJECTION_SLACK {
+        break;
+      }
+      found.push((entry.data, distance_haversine(origin, &self.locations[entry.data])));
+      found.sort_by(|a, b| a.1.total_cmp(&b.1));
+    }
+    found.truncate(n);
+    found
+  }
+
+  /// Entries within `radius` meters of `origin` accepted by `filter`, nearest first.
+  pub fn within_radius(
+    &self,
+    origin: &Location,
+    radius: f64,
+    filter: impl Fn(usize) -> bool,
+  ) -> Vec<(usize, f64)> {
+    let reach = radius * PROJECTION_SLACK;
+    let mut found = self
+      .tree
+      .locate_within_distance(self.project(origin), reach * reach)
+      .filter(|entry| filter(entry.data))
+      .map(|entry| (entry.data, distance_haversine(origin, &self.locations[entry.data])))
+      .filter(|(_, distance)| *distance <= radius)
+      .collect::<Vec<_>>();
+    found.sort_by(|a, b| a.1.total_cmp(&b.1));
+    found
+  }
+
+  /// Entries inside the box spanned by two opposite corners.
+  pub fn in_bounds(&self, corner1: &Location, corner2: &Location) -> Vec<usize> {
+    let envelope = AABB::from_corners(self.project(corner1), self.project(corner2));
+    self.tree.locate_in_envelope(&envelope).map(|entry| entry.data).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn grid() -> Vec<Location> {
+    // 7 x 7 points 0.01° apart around Tehran, roughly 900 m by 1100 m apart.
+    (0..49)
+      .map(|i| Location { x: 51.35 + (i % 7) as f64 * 0.01, y: 35.65 + (i / 7) as f64 * 0.01 })
+      .collect()
+  }
+
+  fn brute_force(locations: &[Location], origin: &Location) -> Vec<(usize, f64)> {
+    let mut all = locations
+      .iter()
+      .enumerate()
+      .map(|(i, f)| (i, distance_haversine(origin, f)))
+      .collect::<Vec<_>>();
+    all.sort_by(|a, b| a.1.total_cmp(&b.1));
+    all
+  }
+
+  #[test]
+  fn nearest_matches_brute_force() {
+    let locations = grid();
+    let index = SpatialIndex::build(&locations);
+    let origin = Location { x: 51.383, y: 35.678 };
+    let expected = brute_force(&locations, &origin);
+    for n in [1, 5, 49, 60] {
+      let found = index.nearest(&origin, n, |_| true);
+      assert_eq!(found, expected[..n.min(locations.len())], "n = {n}");
+    }
+  }
+
+  #[test]
+  fn nearest_applies_the_filter() {
+    let locations = grid();
+    let index = SpatialIndex::build(&locations);
+    let origin = Location { x: 51.35, y: 35.65 };
+    let found = index.nearest(&origin, 3, |i| i % 2 == 1);
+    let expected = brute_force(&locations, &origin).into_iter().filter(|f| f.0 % 2 == 1);
+    assert_eq!(found, expected.take(3).collect::<Vec<_>>());
+  }
+
+  #[test]
+  fn nearest_zero_is_empty() {
+    let index = SpatialIndex::build(&grid());
+    assert!(index.nearest(&Location { x: 51.38, y: 35.68 }, 0, |_| true).is_empty());
+  }
+
+  #[test]
+  fn empty_index_finds_nothing() {
+    let index = SpatialIndex::build(&[]);
+    let origin = Location { x: 51.38, y: 35.68 };
+    assert!(index.nearest(&origin, 3, |_| true).is_empty());
+    assert!(index.within_radius(&origin, 5_000.0, |_| true).is_empty());
+    assert!(index
+      .in_bounds(&Location { x: 51.0, y: 35.0 }, &Location { x: 52.0, y: 36.0 })
+      .is_empty());
+  }
+
+  #[test]
+  fn within_radius_matches_brute_force() {
+    let locations = grid();
+    let index = SpatialIndex::build(&locations);
+    let origin = Location { x: 51.383, y: 35.678 };
+    for radius in [0.0, 500.0, 1_500.0, 3_000.0] {
+      let expected =
+        brute_force(&locations, &origin).into_iter().filter(|f| f.1 <= radius).collect::<Vec<_>>();
+      assert_eq!(index.within_radius(&origin, radius, |_| true), expected, "radius = {radius}");
+    }
+  }
+
+  #[test]
+  fn in_bounds_takes_corners_in_any_order() {
+    let locations = grid();
+    let index = SpatialIndex::build(&locations);
+    let (low, high) = (Location { x: 51.355, y: 35.655 }, Location { x: 51.375, y: 35.675 });
+    let mut expected = (0..locations.len())
+      .filter(|&i| (low.x..=high.x).contains(&locations[i].x))
+      .filter(|&i| (low.y..=high.y).contains(&locations[i].y))
+      .collect::<Vec<_>>();
+    assert_eq!(expected.len(), 4);
+    for (a, b) in [(&low, &high), (&high, &low)] {
+      let mut found = index.in_bounds(a, b);
+      found.sort();
+      expected.sort();
+      assert_eq!(found, expected);
+    }
+  }
+}
@@ -0,0 +1,166 @@
+//! A small hand-built catalog for unit tests: three categories like the shipped data pack, and a
+//! few places around central Tehran whose tags and attributes tests can rely on.
+
+use serde_json::{json, Value};
+
+use crate::{CatalogManifest, PlaceCatalog, PlaceType};
+
+pub(crate) const HISTORY: &str = "مکان تاریخی";
+pub(crate) const MUSEUM: &str = "موزه";
+pub(crate) const RESTAURANT: &str = "رستوران";
+
+pub(crate) fn place_type(label: &str) -> PlaceType {
+    PlaceType::new(label)
+}
+
+fn place(title: &str, kind: &str, (x, y): (f64, f64), tags: &[&str], attributes: Value) -> Value {
+    json!({
+      "title": title,
+      "category": kind,
+      "type": kind,
+      "region": "تهران",
+      "neighbourhood": "",
+      "location": { "x": x, "y": y },
+      "tags": tags,
+      "attributes": attributes,
+    })
+}
+
+fn model(items: Vec<Value>) -> String {
+    let mut pool = Vec::<Value>::new();
+    for tag in items.iter().flat_map(|f| f["tags"].as_array().unwrap()) {
+        if !pool.contains(tag) {
+            pool.push(tag.clone());
+        }
+    }
+    json!({ "tag_pool": pool, "items": items }).to_string()
+}
+
+pub(crate) fn manifest() -> CatalogManifest {
+    serde_json::from_value(json!({
+      "version": "test",
+      "categories": [
+        {
+          "place_type": HISTORY,
+          "label_fa_plural": "مکان های تاریخی",
+          "label_en": "Historical place",
+          "icon": "fa-fort-awesome",
+          "visit_minutes": 60,
+          "source_kinds": ["historical"],
+          "keywords": ["تاریخی", "کاخ", "عمارت"],
+          "constraints": ["price", "wheelchair"],
+          "file": "history.json"
+        },
+        {
+          "place_type": MUSEUM,
+          "label_fa_plural": "موزه ها",
+          "label_en": "Museum",
+          "icon": "fa-university",
+          "visit_minutes": 90,
+          "source_kinds": ["museum"],
+          "keywords": ["نمایشگاه", "گالری"],
+          "constraints": ["price", "wheelchair"],
+          "file": "museum.json"
+        },
+        {
+          "place_type": RESTAURANT,
+          "label_fa_plural": "رستوران ها",
+          "label_en": "Restaurant",
+          "icon": "fa-cutlery",
+          "visit_minutes": 75,
+          "source_kinds": ["restaurant"],
+          "keywords": ["غذا", "ناهار", "نهار", "شام", "کافه", "کبابی"],
+          "constraints": ["price", "diet", "wheelchair"],
+          "file": "restaurant.json"
+        }
+      ],
+      "landmarks": [
+        { "name": "میدان انقلاب", "location": { "x": 51.3915, "y": 35.7009 } }
+      ]
+    }))
+    .unwrap()
+}
+
+/// Places of each category, a few hundred meters to a few kilometers apart; only موزه سینما has a
+/// neighbourhood.
+pub(crate) fn catalog() -> PlaceCatalog {
+    let none = json!({});
+    let mut cinema = place(
+        "موزه سینما",
+        MUSEUM,
+        (51.4140, 35.7920),
+        &["سینما", "هنر"],
+        none.clone(),
+    );
+    cinema["neighbourhood"] = json!("محله تجریش");
+    let history = vec![
+        place(
+            "کاخ گلستان",
+            HISTORY,
+            (51.4200, 35.6800),
+            &["دوره قاجار", "معماری"],
+            json!({ "price_level": "moderate", "wheelchair": true }),
+        ),
+        place(
+            "عمارت مسعودیه",
+            HISTORY,
+            (51.4280, 35.6880),
+            &["دوره قاجار"],
+            none.clone(),
+        ),
+        place(
+            "برج آزادی",
+            HISTORY,
+            (51.3380, 35.6990),
+            &["معماری مدرن"],
+            none.clone(),
+        ),
+    ];
+    let museums = vec![
+        place(
+            "موزه ملی ایران",
+            MUSEUM,
+            (51.4150, 35.6870),
+            &["باستان شناسی", "تاریخ"],
+            json!({ "price_level": "cheap", "wheelchair": true }),
+        ),
+        place(
+            "موزه فرش",
+            MUSEUM,
+            (51.4080, 35.7110),
+            &["فرش", "هنر"],
+            json!({ "price_level": "moderate" }),
+        ),
+        cinema,
+    ];
+    let restaurants = vec![
+        place(
+            "رستوران شهرزاد",
+            RESTAURANT,
+            (51.4210, 35.6830),
+            &["سنتی", "ایرانی"],
+            json!({ "price_level": "cheap", "diets": ["vegetarian"], "wheelchair": true }),
+        ),
+        place(
+            "کافه باغ",
+            RESTAURANT,
+            (51.4100, 35.7050),
+            &["کافه"],
+            json!({ "price_level": "moderate", "diets": ["vegetarian", "vegan"] }),
+        ),
+        place(
+            "کبابی ناصر",
+            RESTAURANT,
+            (51.3400, 35.7000),
+            &["سنتی", "کباب"],
+            json!({ "price_level": "cheap" }),
+        ),
+    ];
+    PlaceCatalog::load(manifest(), |file| match file {
+        "history.json" => Ok(model(history.clone())),
+        "museum.json" => Ok(model(museums.clone())),
+        "restaurant.json" => Ok(model(restaurants.clone())),
+        _ => Err("no such file".to_string()),
+    })
+    .unwrap()
+}
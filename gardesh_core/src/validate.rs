@@ -0,0 +1,304 @@
+//! Checking a model's [`PromptAnalyses`] against the catalog. The response schema pins
+//! `place_type` to the registered categories, but tags are free text and models invent them;
+//! a tag outside its category's pool matches nothing, so it is mapped to the closest pool tag or
+//! dropped.
+
+use serde::Serialize;
+use std::fmt::{self, Display};
+
+use crate::conversation::PlanEdit;
+use crate::{normalize, PlaceCatalog, PlaceInfo, PlaceType, PromptAnalyses, StopOrder};
+
+/// Largest [`tag_distance`] at which an unknown tag is still read as a misspelled pool tag.
+pub const MAX_TAG_DISTANCE: f64 = 0.34;
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TagCorrection {
+  /// A place type with no category; its whole [`crate::PlaceInfo`] is removed.
+  UnknownType { place_type: PlaceType },
+  /// A tag outside the pool, replaced by the closest pool tag.
+  Repaired { place_type: PlaceType, from: String, to: String, distance: f64 },
+  /// A tag outside the pool with no pool tag close enough.
+  Dropped { place_type: PlaceType, tag: String },
+}
+
+/// What [`repair`] changed. `nothing_valid` is set when the model asked for something but none
+/// of it survived, i.e. the analysis is worth asking again for.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct TagReport {
+  pub corrections: Vec<TagCorrection>,
+  pub nothing_valid: bool,
+}
+
+impl TagReport {
+  pub fn is_clean(&self) -> bool {
+    self.corrections.is_empty()
+  }
+
+  /// Follow-up message for the model listing what it got wrong, in the system prompt's language.
+  pub fn feedback(&self) -> String {
+    let mut text = String::from("پاسخ قبلی با لیست ها سازگار نبود:\n");
+    for correction in &self.corrections {
+      match correction {
+        TagCorrection::UnknownType { place_type } => {
+          text += &format!("- نوع مکان «{place_type}» وجود ندارد.\n")
+        }
+        TagCorrection::Repaired { .. } => {}
+        TagCorrection::Dropped { place_type, tag } => {
+          text += &format!("- تگ «{tag}» در لیست تگ های {place_type} نیست.\n")
+        }
+      }
+    }
+    text + "دوباره پاسخ بده و فقط از نوع ها و تگ های همان لیست ها استفاده کن."
+  }
+}
+
+impl Display for TagReport {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for correction in &self.corrections {
+      match correction {
+        TagCorrection::UnknownType { place_type } => {
+          writeln!(f, "unknown place type `{place_type}`")?
+        }
+        TagCorrection::Repaired { place_type, from, to, distance } => {
+          writeln!(f, "{place_type}: `{from}` -> `{to}` (distance {distance:.2})")?
+        }
+        TagCorrection::Dropped { place_type, tag } => writeln!(f, "{place_type}: dropped `{tag}`")?,
+      }
+    }
+    write!(f, "{} corrections", self.corrections.len())
+  }
+}
+
+/// Levenshtein distance over characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, ca) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = diagonal + usize::from(ca != *cb);
+      diagonal = row[j + 1];
+      row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+  row[b.len()]
+}
+
+/// Edit distance of the [`normalize`]d tags relative to the longer one: 0 for the same tag, 1 for
+/// tags with nothing in common.
+pub fn tag_distance(a: &str, b: &str) -> f64 {
+  let (a, b) = (normalize(a), normalize(b));
+  let longest = a.chars().count().max(b.chars().count());
+  if longest == 0 {
+    return 0.0;
+  }
+  edit_distance(&a, &b) as f64 / longest as f64
+}
+
+/// Brings the tags of one info into its category's pool, recording changes in `report`. Returns
+/// false, leaving the info as is, when its place type has no category. An order relative to a
+/// place type with no category becomes flexible.
+pub fn repair_info(catalog: &PlaceCatalog, info: &mut PlaceInfo, report: &mut TagReport) -> bool {
+  if catalog.category(&info.place_type).is_none() {
+    report.corrections.push(TagCorrection::UnknownType { place_type: info.place_type.clone() });
+    return false;
+  }
+  let pool = catalog.tag_pool(&info.place_type);
+  let mut tags = Vec::<String>::new();
+  for tag in &info.tags {
+    let closest = pool
+      .iter()
+      .map(|pool_tag| (tag_distance(tag, pool_tag), pool_tag))
+      .min_by(|a, b| a.0.total_cmp(&b.0));
+    let place_type = info.place_type.clone();
+    let repaired = match closest {
+      Some((0.0, pool_tag)) => Some(pool_tag.clone()),
+      Some((distance, pool_tag)) if distance <= MAX_TAG_DISTANCE => {
+        report.corrections.push(TagCorrection::Repaired {
+          place_type,
+          from: tag.clone(),
+          to: pool_tag.clone(),
+          distance,
+        });
+        Some(pool_tag.clone())
+      }
+      _ => {
+        report.corrections.push(TagCorrection::Dropped { place_type, tag: tag.clone() });
+        None
+      }
+    };
+    if let Some(tag) = repaired.filter(|f| !tags.contains(f)) {
+      tags.push(tag);
+    }
+  }
+  info.tags = tags;
+  if let StopOrder::Before { place_type } | StopOrder::After { place_type } = &info.order {
+    if catalog.category(place_type).is_none() {
+      info.order = StopOrder::Flexible;
+    }
+  }
+  true
+}
+
+/// Brings every tag of `analyses` into its category's pool, in place.
+///
+/// Pool tags are kept in the pool's spelling, near misses within [`MAX_TAG_DISTANCE`] are
+/// replaced, the rest are dropped, and infos of unknown place types are removed.
+pub fn repair(catalog: &PlaceCatalog, analyses: &mut PromptAnalyses) -> TagReport {
+  let mut report = TagReport::default();
+  let asked_for_tags = analyses.place_infos.iter().any(|f| !f.tags.is_empty());
+  let asked_for_places = !analyses.place_infos.is_empty();
+  analyses.place_infos.retain_mut(|info| repair_info(catalog, info, &mut report));
+
+  let has_tags = analyses.place_infos.iter().any(|f| !f.tags.is_empty());
+  report.nothing_valid =
+    (asked_for_places && analyses.place_infos.is_empty()) || (asked_for_tags && !has_tags);
+  report
+}
+
+/// [`repair`] for the edits of a follow-up: the infos they add or replace with are repaired, and
+/// edits naming a place type with no category are removed.
+pub fn repair_edits(catalog: &PlaceCatalog, edits: &mut Vec<PlanEdit>) -> TagReport {
+  let mut report = TagReport::default();
+  let has_tags = |edits: &[PlanEdit]| {
+    edits.iter().any(|f| {
+      matches!(f, PlanEdit::Add { info } | PlanEdit::Replace { info, .. } if !info.tags.is_empty())
+    })
+  };
+  let asked_for_tags = has_tags(edits);
+  let asked_for_edits = !edits.is_empty();
+  edits.retain_mut(|edit| match edit {
+    PlanEdit::Add { info } | PlanEdit::Replace { info, .. } => {
+      repair_info(catalog, info, &mut report)
+    }
+    PlanEdit::Remove { place_type, .. } => {
+      let known = catalog.category(place_type).is_some();
+      if !known {
+        report.corrections.push(TagCorrection::UnknownType { place_type: place_type.clone() });
+      }
+      known
+    }
+  });
+
+  report.nothing_valid =
+    (asked_for_edits && edits.is_empty()) || (asked_for_tags && !has_tags(edits));
+  report
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::testing::{catalog, place_type, MUSEUM, RESTAURANT};
+
+  fn info(label: &str, tags: &[&str]) -> PlaceInfo {
+    PlaceInfo {
+      place_type: place_type(label),
+      tags: tags.iter().map(|f| f.to_string()).collect(),
+      count: None,
+      order: StopOrder::Flexible,
+    }
+  }
+
+  fn analyses(place_infos: Vec<PlaceInfo>) -> PromptAnalyses {
+    PromptAnalyses { place_infos, ..Default::default() }
+  }
+
+  #[test]
+  fn tag_distance_is_relative_to_the_longer_tag() {
+    assert_eq!(tag_distance("معماري", "معماری"), 0.0);
+    assert_eq!(tag_distance("", ""), 0.0);
+    assert_eq!(tag_distance("هنر", "هنرها"), 0.4);
+    assert_eq!(tag_distance("فرش", "کباب"), 1.0);
+    assert_eq!(tag_distance("هنری", "هنر"), tag_distance("هنر", "هنری"));
+  }
+
+  #[test]
+  fn repairs_within_the_threshold_and_drops_the_rest() {
+    let catalog = catalog();
+    // One edit in three characters is 0.33, within the threshold; two in five is 0.4.
+    let mut found = analyses(vec![info(MUSEUM, &["هنز", "هنرها", "فرش"])]);
+    let report = repair(&catalog, &mut found);
+    assert_eq!(found.place_infos[0].tags, ["هنر", "فرش"]);
+    let museum = place_type(MUSEUM);
+    assert_eq!(
+      report.corrections,
+      [
+        TagCorrection::Repaired {
+          place_type: museum.clone(),
+          from: "هنز".to_string(),
+          to: "هنر".to_string(),
+          distance: 1.0 / 3.0,
+        },
+        TagCorrection::Dropped { place_type: museum, tag: "هنرها".to_string() },
+      ]
+    );
+    assert!(!report.nothing_valid);
+  }
+
+  #[test]
+  fn pool_spelling_wins_and_duplicates_collapse() {
+    let mut found = analyses(vec![info(MUSEUM, &["هنر", "هنز", "فرش"])]);
+    let report = repair(&catalog(), &mut found);
+    assert_eq!(found.place_infos[0].tags, ["هنر", "فرش"]);
+    assert_eq!(report.corrections.len(), 1);
+  }
+
+  #[test]
+  fn ties_go_to_the_first_pool_tag() {
+    let mut catalog = catalog();
+    let restaurant = place_type(RESTAURANT);
+    for pool in [["کبابی", "کباب"], ["کباب", "کبابی"]] {
+      let category =
+        catalog.categories.iter_mut().find(|f| f.def.place_type == restaurant).unwrap();
+      category.data.tag_pool = pool.map(String::from).to_vec();
+      // `کبابا` is one edit away from both, a distance of 0.2.
+      let mut found = analyses(vec![info(RESTAURANT, &["کبابا"])]);
+      repair(&catalog, &mut found);
+      assert_eq!(found.place_infos[0].tags, [pool[0]]);
+    }
+  }
+
+  #[test]
+  fn unknown_types_are_removed() {
+    let mut found = analyses(vec![info("سینما", &[]), info(MUSEUM, &[])]);
+    found.place_infos[1].order = StopOrder::After { place_type: place_type("سینما") };
+    let report = repair(&catalog(), &mut found);
+    assert_eq!(found.place_infos, [info(MUSEUM, &[])]);
+    assert_eq!(
+      report.corrections,
+      [TagCorrection::UnknownType { place_type: place_type("سینما") }]
+    );
+    assert!(!report.nothing_valid);
+  }
+
+  #[test]
+  fn nothing_valid_when_every_tag_or_type_is_lost() {
+    let mut found = analyses(vec![info(MUSEUM, &["ورزشی"])]);
+    assert!(repair(&catalog(), &mut found).nothing_valid);
+    assert!(found.place_infos[0].tags.is_empty());
+    let mut found = analyses(vec![info("سینما", &[])]);
+    assert!(repair(&catalog(), &mut found).nothing_valid);
+    let mut found = analyses(vec![]);
+    assert!(!repair(&catalog(), &mut found).nothing_valid);
+  }
+
+  #[test]
+  fn edits_are_repaired_like_analyses() {
+    let mut edits = vec![
+      PlanEdit::Add { info: info(MUSEUM, &["ورزشی"]) },
+      PlanEdit::Remove { stop: None, place_type: place_type("سینما") },
+    ];
+    let report = repair_edits(&catalog(), &mut edits);
+    assert_eq!(edits, [PlanEdit::Add { info: info(MUSEUM, &[]) }]);
+    assert_eq!(report.corrections.len(), 2);
+    assert!(report.nothing_valid);
+
+    let mut edits = vec![PlanEdit::Remove { stop: Some(0), place_type: place_type(RESTAURANT) }];
+    let report = repair_edits(&catalog(), &mut edits);
+    assert!(report.is_clean() && !report.nothing_valid);
+    assert_eq!(edits.len(), 1);
+  }
+}
@@ -0,0 +1,43 @@
+/// Escapes text for use in XML element content and attribute values.
+pub(crate) fn escape(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\'' => out.push_str("&apos;"),
+      c => out.push(c),
+    }
+  }
+  out
+}
+
+/// Description shared by the GPX and KML exports: the place's type and tags.
+pub(crate) fn place_description(place: &crate::Place) -> String {
+  format!("Type: {}\nTags: {}", place.r#type, place.tags.join(", "))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+  use super::*;
+
+  /// Titles with every character XML reserves.
+  pub(crate) const RESERVED: &str = "کافه \"نقش\" & <باغ> 'ایرانی'";
+
+  /// Parses `text`, panicking with the parser's message when it is not well-formed XML.
+  pub(crate) fn parse(text: &str) -> roxmltree::Document<'_> {
+    roxmltree::Document::parse(text).unwrap_or_else(|err| panic!("{err}:\n{text}"))
+  }
+
+  #[test]
+  fn escaped_text_reads_back() {
+    let text = format!("<a b=\"{0}\" c='{0}'>{0}</a>", escape(RESERVED));
+    let document = parse(&text);
+    let root = document.root_element();
+    assert_eq!(root.text(), Some(RESERVED));
+    assert_eq!(root.attribute("b"), Some(RESERVED));
+    assert_eq!(root.attribute("c"), Some(RESERVED));
+  }
+}
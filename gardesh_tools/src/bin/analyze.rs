@@ -0,0 +1,121 @@
+//! Sends one prompt to a configured model and prints the resulting analysis as JSON, with the tag
+//! corrections made to it on stderr; for checking endpoint, proxy and header settings before they
+//! go into the app.
+//!
+//! ```sh
+//! cargo run -p gardesh_tools --bin analyze -- --provider ollama --model qwen2.5:7b "دو موزه"
+//! cargo run -p gardesh_tools --bin analyze -- --config settings.json --proxy socks5h://127.0.0.1:1080 \
+//!   "یک رستوران سنتی"
+//! cargo run -p gardesh_tools --bin analyze -- --fixtures replay "دو موزه"
+//! ```
+
+use clap::Parser;
+use gardesh_core::{FixtureMode, LlmAnalyzer, LlmConfig, LlmProvider, PromptAnalyzer};
+use gardesh_tools::{load_catalog, load_llm_config, parse_fixture_mode, parse_provider};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+fn parse_header(text: &str) -> Result<(String, String), String> {
+  let (name, value) = text.split_once(':').ok_or(format!("`{text}` is not `name: value`"))?;
+  Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+#[derive(Parser)]
+struct Args {
+  prompt: String,
+  /// Directory holding the data pack's manifest.json.
+  #[arg(long, default_value = "public/catalog")]
+  catalog: PathBuf,
+  /// JSON file with an `LlmConfig`, or a settings file with one under `llm`; flags override it.
+  #[arg(long)]
+  config: Option<PathBuf>,
+  #[arg(long, value_parser = parse_provider)]
+  provider: Option<LlmProvider>,
+  #[arg(long)]
+  model: Option<String>,
+  #[arg(long)]
+  base_url: Option<String>,
+  /// `http://`, `https://` or `socks5h://` proxy for every request to the model.
+  #[arg(long)]
+  proxy: Option<String>,
+  #[arg(long)]
+  timeout_secs: Option<u64>,
+  /// Extra request header as `name: value`; may be repeated.
+  #[arg(long = "header", value_parser = parse_header)]
+  headers: Vec<(String, String)>,
+  /// Ask the model again when none of its types or tags are in the catalog.
+  #[arg(long)]
+  reask: bool,
+  /// `record` saves every exchange; `replay` answers from saved ones without a model.
+  #[arg(long, value_parser = parse_fixture_mode)]
+  fixtures: Option<FixtureMode>,
+  #[arg(long)]
+  fixture_dir: Option<String>,
+  /// Tags of each pool to list in the system prompt.
+  #[arg(long, conflicts_with = "all_tags")]
+  prompt_tags: Option<usize>,
+  /// List whole tag pools in the system prompt.
+  #[arg(long)]
+  all_tags: bool,
+  /// Defaults to the provider's usual environment variable.
+  #[arg(long)]
+  api_key: Option<String>,
+}
+
+fn config(args: &Args) -> Result<LlmConfig, String> {
+  let mut config = match &args.config {
+    Some(path) => load_llm_config(path)?,
+    None => LlmConfig::default(),
+  };
+  if let Some(provider) = args.provider {
+    config.provider = provider;
+  }
+  if let Some(model) = &args.model {
+    config.model = model.clone();
+  }
+  config.base_url = args.base_url.clone().or(config.base_url);
+  config.proxy = args.proxy.clone().or(config.proxy);
+  config.timeout_secs = args.timeout_secs.or(config.timeout_secs);
+  config.headers.extend(args.headers.iter().cloned());
+  config.reask_on_invalid |= args.reask;
+  config.fixture_mode = args.fixtures.unwrap_or(config.fixture_mode);
+  config.fixture_dir = args.fixture_dir.clone().or(config.fixture_dir);
+  if args.all_tags {
+    config.prompt_tags = None;
+  } else if args.prompt_tags.is_some() {
+    config.prompt_tags = args.prompt_tags;
+  }
+  if let Some(api_key) = &args.api_key {
+    config.api_key = api_key.clone();
+  }
+  Ok(config)
+}
+
+async fn run(args: &Args) -> Result<String, String> {
+  let catalog = load_catalog(&args.catalog)?;
+  let analyzer = LlmAnalyzer::new(config(args)?).map_err(|err| err.to_string())?;
+  eprintln!("asking {}", analyzer.name());
+  let checked =
+    analyzer.analyze_checked(&catalog, &args.prompt).await.map_err(|err| err.to_string())?;
+  if checked.reasked {
+    eprintln!("re-asked after an answer with nothing valid in it");
+  }
+  eprintln!("{}", checked.prompt_size);
+  eprintln!("{}", checked.report);
+  Ok(serde_json::to_string_pretty(&checked.analyses).unwrap())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+  let args = Args::parse();
+  match run(&args).await {
+    Ok(analyses) => {
+      println!("{analyses}");
+      ExitCode::SUCCESS
+    }
+    Err(err) => {
+      eprintln!("error: {err}");
+      ExitCode::FAILURE
+    }
+  }
+}
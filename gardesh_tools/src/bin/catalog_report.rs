@@ -0,0 +1,52 @@
+//! Checks a catalog data pack and reports problems; exits non-zero when any error is found.
+//!
+//! ```sh
+//! cargo run -p gardesh_tools --bin catalog_report -- public/catalog --format json
+//! ```
+
+use clap::{Parser, ValueEnum};
+use gardesh_core::quality::QualityReport;
+use gardesh_tools::load_catalog;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+  Text,
+  Json,
+}
+
+#[derive(Parser)]
+struct Args {
+  /// Directory holding the data pack's manifest.json.
+  #[arg(default_value = "public/catalog")]
+  catalog: PathBuf,
+  #[arg(long, value_enum, default_value_t = Format::Text)]
+  format: Format,
+}
+
+fn run(args: &Args) -> Result<QualityReport, String> {
+  let catalog = load_catalog(&args.catalog)?;
+  Ok(QualityReport::check(&catalog))
+}
+
+fn main() -> ExitCode {
+  let args = Args::parse();
+  match run(&args) {
+    Ok(report) => {
+      match args.format {
+        Format::Text => println!("{report}"),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+      }
+      if report.has_errors() {
+        ExitCode::FAILURE
+      } else {
+        ExitCode::SUCCESS
+      }
+    }
+    Err(err) => {
+      eprintln!("error: {err}");
+      ExitCode::from(2)
+    }
+  }
+}
@@ -0,0 +1,180 @@
+//! Scores analyzers against the golden prompts in `gemini_model_compare/golden.json`: tag
+//! precision and recall, place-type accuracy, `total_count` accuracy and latency, per model, along
+//! with the system prompt size the tag selection leaves.
+//!
+//! ```sh
+//! cargo run -p gardesh_tools --bin eval -- --offline --model gemini-2.0-flash-exp \
+//!   --model gemini-1.5-pro
+//! cargo run -p gardesh_tools --bin eval -- --config settings.json --fixtures replay --cases
+//! ```
+
+use clap::{Parser, ValueEnum};
+use gardesh_core::eval::{CaseRun, GoldenSet, ModelScore};
+use gardesh_core::prefilter::{PromptSavings, PromptSize};
+use gardesh_core::{
+  FixtureMode, LlmAnalyzer, LlmConfig, LlmProvider, OfflineAnalyzer, PlaceCatalog, PromptAnalyzer,
+};
+use gardesh_tools::{load_catalog, load_llm_config, parse_fixture_mode, parse_provider};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+  Text,
+  Json,
+}
+
+#[derive(Parser)]
+struct Args {
+  #[arg(long, default_value = "gemini_model_compare/golden.json")]
+  golden: PathBuf,
+  /// Directory holding the data pack's manifest.json.
+  #[arg(long, default_value = "public/catalog")]
+  catalog: PathBuf,
+  /// Model config (or settings file) to evaluate; may be repeated.
+  #[arg(long)]
+  config: Vec<PathBuf>,
+  /// Model of `--provider` to evaluate with its default settings; may be repeated.
+  #[arg(long)]
+  model: Vec<String>,
+  #[arg(long, value_parser = parse_provider, default_value = "gemini")]
+  provider: LlmProvider,
+  /// Evaluate the offline analyzer too; it is the only one when no model is given.
+  #[arg(long)]
+  offline: bool,
+  /// `record` saves every exchange; `replay` scores saved ones without asking any model.
+  #[arg(long, value_parser = parse_fixture_mode)]
+  fixtures: Option<FixtureMode>,
+  #[arg(long)]
+  fixture_dir: Option<String>,
+  /// Tags of each pool to list in the system prompt.
+  #[arg(long, conflicts_with = "all_tags")]
+  prompt_tags: Option<usize>,
+  /// List whole tag pools in the system prompt, to compare against a selection.
+  #[arg(long)]
+  all_tags: bool,
+  /// List every case under its model's totals.
+  #[arg(long)]
+  cases: bool,
+  #[arg(long, value_enum, default_value_t = Format::Text)]
+  format: Format,
+}
+
+fn load_golden(path: &Path) -> Result<GoldenSet, String> {
+  let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+  serde_json::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn configs(args: &Args) -> Result<Vec<LlmConfig>, String> {
+  let mut configs =
+    args.config.iter().map(|path| load_llm_config(path)).collect::<Result<Vec<_>, _>>()?;
+  configs.extend(
+    args
+      .model
+      .iter()
+      .map(|model| LlmConfig { model: model.clone(), ..LlmConfig::new(args.provider) }),
+  );
+  for config in &mut configs {
+    config.fixture_mode = args.fixtures.unwrap_or(config.fixture_mode);
+    config.fixture_dir = args.fixture_dir.clone().or(config.fixture_dir.take());
+    if args.all_tags {
+      config.prompt_tags = None;
+    } else if args.prompt_tags.is_some() {
+      config.prompt_tags = args.prompt_tags;
+    }
+  }
+  Ok(configs)
+}
+
+async fn evaluate(
+  analyzer: &impl PromptAnalyzer,
+  catalog: &PlaceCatalog,
+  golden: &GoldenSet,
+) -> ModelScore {
+  let mut runs = Vec::new();
+  for case in &golden.cases {
+    let start = Instant::now();
+    let answer = analyzer.analyze(catalog, &case.prompt).await.map_err(|err| err.to_string());
+    runs.push(CaseRun::new(case, start.elapsed().as_millis() as u64, answer));
+  }
+  ModelScore::new(analyzer.name(), runs)
+}
+
+/// System prompt size for the golden prompts, averaged.
+fn prompt_size(
+  analyzer: &LlmAnalyzer,
+  catalog: &PlaceCatalog,
+  golden: &GoldenSet,
+) -> PromptSavings {
+  let sizes = golden
+    .cases
+    .iter()
+    .map(|case| PromptSavings::measure(catalog, &analyzer.tag_selection(catalog, &case.prompt)))
+    .collect::<Vec<_>>();
+  let mean =
+    |f: fn(&PromptSavings) -> usize| sizes.iter().map(f).sum::<usize>() / sizes.len().max(1);
+  PromptSavings {
+    full: PromptSize { tags: mean(|f| f.full.tags), chars: mean(|f| f.full.chars) },
+    selected: PromptSize { tags: mean(|f| f.selected.tags), chars: mean(|f| f.selected.chars) },
+  }
+}
+
+async fn run(args: &Args) -> Result<Vec<ModelScore>, String> {
+  let catalog = load_catalog(&args.catalog)?;
+  let golden = load_golden(&args.golden)?;
+  let configs = configs(args)?;
+  let mut scores = Vec::new();
+  if args.offline || configs.is_empty() {
+    scores.push(evaluate(&OfflineAnalyzer, &catalog, &golden).await);
+  }
+  for config in configs {
+    let analyzer = LlmAnalyzer::new(config).map_err(|err| err.to_string())?;
+    eprintln!("evaluating {}", analyzer.name());
+    let mut score = evaluate(&analyzer, &catalog, &golden).await;
+    score.prompt_size = Some(prompt_size(&analyzer, &catalog, &golden));
+    scores.push(score);
+  }
+  Ok(scores)
+}
+
+fn print_cases(score: &ModelScore) {
+  for run in &score.runs {
+    let s = &run.score;
+    let types = if s.types_correct { "types ok" } else { "types wrong" };
+    let total = if s.total_count_correct { "total ok" } else { "total wrong" };
+    println!(
+      "  {}: {types}, {total}, tags {}/{} given {}, {} ms",
+      run.prompt, s.tags_matched, s.tags_expected, s.tags_given, run.latency_ms
+    );
+    if let Some(err) = &run.error {
+      println!("    error: {err}");
+    }
+  }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+  let args = Args::parse();
+  match run(&args).await {
+    Ok(scores) => {
+      match args.format {
+        Format::Text => {
+          for score in &scores {
+            println!("{score}");
+            if args.cases {
+              print_cases(score);
+            }
+          }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&scores).unwrap()),
+      }
+      ExitCode::SUCCESS
+    }
+    Err(err) => {
+      eprintln!("error: {err}");
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,150 @@
+//! Local stand-in for Gemini's `generateContent` endpoint, for trying endpoint, proxy, timeout
+//! and header settings without a key or network access.
+//!
+//! Every request is answered with the offline analysis of its first user message, wrapped in the
+//! response shape Gemini uses. Request lines and headers are echoed to stdout.
+//!
+//! ```sh
+//! cargo run -p gardesh_tools --bin fake_gemini -- --port 8787
+//! cargo run -p gardesh_tools --bin analyze -- --base-url http://127.0.0.1:8787/v1beta/ \
+//!   --api-key test "دو موزه و یک رستوران سنتی"
+//! ```
+
+use clap::Parser;
+use gardesh_core::offline::analyze_offline;
+use gardesh_core::PlaceCatalog;
+use gardesh_tools::load_catalog;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Parser)]
+struct Args {
+  #[arg(long, default_value_t = 8787)]
+  port: u16,
+  /// Directory holding the data pack's manifest.json.
+  #[arg(long, default_value = "public/catalog")]
+  catalog: PathBuf,
+  /// Wait this long before answering, to exercise client timeouts.
+  #[arg(long, default_value_t = 0)]
+  delay_ms: u64,
+  /// Answer every request with this HTTP status and a Gemini error body instead.
+  #[arg(long)]
+  fail_status: Option<u16>,
+  /// Answer first turns with tags in no pool, to exercise tag repair and the re-ask.
+  #[arg(long)]
+  invent_tags: bool,
+}
+
+struct Request {
+  line: String,
+  headers: Vec<(String, String)>,
+  body: Vec<u8>,
+}
+
+fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+  let mut reader = BufReader::new(stream);
+  let mut line = String::new();
+  reader.read_line(&mut line).map_err(|err| err.to_string())?;
+  let mut headers = Vec::new();
+  loop {
+    let mut header = String::new();
+    reader.read_line(&mut header).map_err(|err| err.to_string())?;
+    let header = header.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+    }
+  }
+  let length = headers
+    .iter()
+    .find(|(name, _)| name == "content-length")
+    .and_then(|(_, value)| value.parse::<usize>().ok())
+    .unwrap_or(0);
+  let mut body = vec![0; length];
+  reader.read_exact(&mut body).map_err(|err| err.to_string())?;
+  Ok(Request { line: line.trim_end().to_string(), headers, body })
+}
+
+/// Texts of the `user` turns of a `generateContent` body.
+fn user_texts(body: &Value) -> Vec<&str> {
+  let contents = body["contents"].as_array().map(Vec::as_slice).unwrap_or_default();
+  contents
+    .iter()
+    .filter(|f| f["role"] == "user")
+    .filter_map(|f| f["parts"][0]["text"].as_str())
+    .collect()
+}
+
+fn respond(catalog: &PlaceCatalog, args: &Args, request: &Request) -> (u16, Value) {
+  if let Some(status) = args.fail_status {
+    return (status, json!({ "error": { "code": status, "message": "fake_gemini failure" } }));
+  }
+  if !request.line.contains(":generateContent") {
+    return (404, json!({ "error": { "code": 404, "message": "only generateContent is served" } }));
+  }
+  let body = serde_json::from_slice::<Value>(&request.body).unwrap_or_default();
+  let texts = user_texts(&body);
+  let Some(prompt) = texts.first() else {
+    return (400, json!({ "error": { "code": 400, "message": "no user message" } }));
+  };
+  let mut analyses = analyze_offline(catalog, prompt);
+  if args.invent_tags && texts.len() == 1 {
+    for info in &mut analyses.place_infos {
+      info.tags = vec!["برچسب ساختگی".to_string()];
+    }
+  }
+  let text = serde_json::to_string(&analyses).unwrap();
+  let response = json!({
+    "candidates": [{ "content": { "parts": [{ "text": text }], "role": "model" } }],
+    "usageMetadata": { "promptTokenCount": 0, "candidatesTokenCount": 0, "totalTokenCount": 0 }
+  });
+  (200, response)
+}
+
+fn serve(catalog: &PlaceCatalog, args: &Args, mut stream: TcpStream) -> Result<(), String> {
+  let request = read_request(&mut stream)?;
+  println!("{}", request.line);
+  for (name, value) in &request.headers {
+    println!("  {name}: {value}");
+  }
+  thread::sleep(Duration::from_millis(args.delay_ms));
+  let (status, body) = respond(catalog, args, &request);
+  let body = body.to_string();
+  let response = format!(
+    "HTTP/1.1 {status} FAKE\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+    body.len()
+  );
+  stream.write_all(response.as_bytes()).map_err(|err| err.to_string())
+}
+
+fn main() -> ExitCode {
+  let args = Args::parse();
+  let catalog = match load_catalog(&args.catalog) {
+    Ok(catalog) => catalog,
+    Err(err) => {
+      eprintln!("error: {err}");
+      return ExitCode::FAILURE;
+    }
+  };
+  let listener = match TcpListener::bind(("127.0.0.1", args.port)) {
+    Ok(listener) => listener,
+    Err(err) => {
+      eprintln!("error: port {}: {err}", args.port);
+      return ExitCode::FAILURE;
+    }
+  };
+  println!("fake Gemini on http://127.0.0.1:{}/v1beta/", args.port);
+  for stream in listener.incoming().flatten() {
+    if let Err(err) = serve(&catalog, &args, stream) {
+      eprintln!("error: {err}");
+    }
+  }
+  ExitCode::SUCCESS
+}
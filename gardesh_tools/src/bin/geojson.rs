@@ -0,0 +1,79 @@
+//! Converts catalog files to and from GeoJSON FeatureCollections.
+//!
+//! ```sh
+//! cargo run -p gardesh_tools --bin geojson -- export \
+//!   public/catalog/neshan_museum_results_unique_with_tags.json --out museums.geojson
+//! cargo run -p gardesh_tools --bin geojson -- import museums.geojson --out museums.json
+//! ```
+
+use clap::{Parser, Subcommand};
+use gardesh_core::geojson::{catalog_from_geojson, catalog_to_geojson};
+use gardesh_core::{CatalogManifest, NeshanDataModel};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+struct Args {
+  #[command(subcommand)]
+  command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+  /// Writes the items of a catalog file as point features.
+  Export {
+    catalog: PathBuf,
+    #[arg(long)]
+    out: PathBuf,
+  },
+  /// Validates a FeatureCollection and writes it as a catalog file.
+  Import {
+    geojson: PathBuf,
+    /// Category registry the feature types are checked against.
+    #[arg(long, default_value = "public/catalog/manifest.json")]
+    manifest: PathBuf,
+    #[arg(long)]
+    out: PathBuf,
+  },
+}
+
+fn read(path: &Path) -> Result<String, String> {
+  fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn write(path: &Path, json: String) -> Result<(), String> {
+  fs::write(path, json + "\n").map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn run(args: Args) -> Result<(), String> {
+  match args.command {
+    Command::Export { catalog, out } => {
+      let mut model = serde_json::from_str::<NeshanDataModel>(&read(&catalog)?)
+        .map_err(|err| format!("{}: {err}", catalog.display()))?;
+      model.ensure_ids();
+      let json = serde_json::to_string_pretty(&catalog_to_geojson(&model)).unwrap();
+      write(&out, json)?;
+      eprintln!("wrote {} features to {}", model.items.len(), out.display());
+    }
+    Command::Import { geojson, manifest, out } => {
+      let manifest = CatalogManifest::parse(&read(&manifest)?).map_err(|err| err.to_string())?;
+      let model = catalog_from_geojson(&read(&geojson)?, &manifest).map_err(|errors| {
+        errors.iter().map(|f| format!("{}: {f}", geojson.display())).collect::<Vec<_>>().join("\n")
+      })?;
+      write(&out, serde_json::to_string_pretty(&model).unwrap())?;
+      eprintln!("wrote {} places to {}", model.items.len(), out.display());
+    }
+  }
+  Ok(())
+}
+
+fn main() -> ExitCode {
+  match run(Args::parse()) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("error: {err}");
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,163 @@
+//! Turns raw Neshan search dumps (`neshan_*_results.json`) into the tagged `NeshanDataModel`
+//! files the app loads from its catalog.
+//!
+//! ```sh
+//! cargo run -p gardesh_tools --bin ingest -- neshan_museum_resaults.json \
+//!   --tags public/catalog/neshan_museum_results_unique_with_tags.json \
+//!   --out public/catalog/neshan_museum_results_unique_with_tags.json
+//! ```
+
+use clap::Parser;
+use gardesh_core::{CatalogManifest, Location, NeshanDataModel, Place, PlaceAttributes, PlaceId};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+struct Args {
+  /// Raw dumps as returned by the Neshan search API.
+  #[arg(required = true)]
+  dumps: Vec<PathBuf>,
+  /// Tagged catalog files whose item ids, tags and attributes are carried over to matching places,
+  /// matched by stored id or else by title and location.
+  #[arg(long)]
+  tags: Vec<PathBuf>,
+  /// Forces every item to this type instead of normalizing each item's `type`.
+  #[arg(long)]
+  r#type: Option<String>,
+  /// Category registry used to normalize raw types.
+  #[arg(long, default_value = "public/catalog/manifest.json")]
+  manifest: PathBuf,
+  #[arg(long)]
+  out: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct RawDump {
+  items: Vec<RawPlace>,
+}
+
+#[derive(Deserialize)]
+struct RawPlace {
+  /// Present when the dump is itself a catalog file being re-ingested.
+  #[serde(default)]
+  id: PlaceId,
+  title: String,
+  #[serde(default)]
+  address: String,
+  category: String,
+  r#type: String,
+  region: String,
+  #[serde(default)]
+  neighbourhood: String,
+  // `z` is always the string "NaN" in the dumps, so it is not read at all.
+  location: Location,
+  #[serde(default)]
+  tags: Vec<String>,
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &PathBuf) -> Result<T, String> {
+  let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+  serde_json::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+fn run(args: Args) -> Result<(), String> {
+  let manifest = read_json::<CatalogManifest>(&args.manifest)?;
+  let forced_type = match &args.r#type {
+    Some(kind) => Some(
+      manifest
+        .place_type_for_source_kind(kind)
+        .ok_or_else(|| format!("unknown place type `{kind}`"))?,
+    ),
+    None => None,
+  };
+
+  // Known places by their stored id, and that id by the one their title and location derive to
+  // now, for rows that carry no id.
+  let mut known = HashMap::new();
+  let mut known_ids = HashMap::new();
+  for path in &args.tags {
+    let model = read_json::<NeshanDataModel>(path)?;
+    for mut place in model.items {
+      place.ensure_id();
+      known_ids.insert(PlaceId::derive(&place.title, &place.location), place.id.clone());
+      known.insert(place.id.clone(), place);
+    }
+  }
+
+  let mut seen = HashSet::new();
+  let mut items = Vec::new();
+  let (mut skipped, mut duplicates) = (0, 0);
+  for path in &args.dumps {
+    for raw in read_json::<RawDump>(path)?.items {
+      let Some(r#type) =
+        forced_type.clone().or_else(|| manifest.place_type_for_source_kind(&raw.r#type))
+      else {
+        eprintln!("skipping `{}`: unknown type `{}`", raw.title, raw.r#type);
+        skipped += 1;
+        continue;
+      };
+      // Only new places get a derived id; a known one keeps the id stored with it.
+      let derived = PlaceId::derive(&raw.title, &raw.location);
+      let id = Some(raw.id)
+        .filter(|f| !f.is_empty())
+        .or_else(|| known_ids.get(&derived).cloned())
+        .unwrap_or(derived);
+      let mut place = Place {
+        id,
+        title: raw.title.trim().to_string(),
+        address: raw.address,
+        category: raw.category,
+        r#type,
+        region: raw.region,
+        neighbourhood: raw.neighbourhood,
+        location: raw.location,
+        tags: raw.tags,
+        attributes: PlaceAttributes::default(),
+      };
+      if let Some(known) = known.get(&place.id) {
+        place.tags.extend(
+          known.tags.iter().filter(|tag| !place.tags.contains(tag)).cloned().collect::<Vec<_>>(),
+        );
+        place.attributes = known.attributes.clone();
+      }
+      if seen.insert(place.id.clone()) {
+        items.push(place);
+      } else {
+        duplicates += 1;
+      }
+    }
+  }
+
+  let mut tag_pool = Vec::new();
+  for tag in items.iter().flat_map(|place| &place.tags) {
+    if !tag_pool.contains(tag) {
+      tag_pool.push(tag.clone());
+    }
+  }
+  let untagged = items.iter().filter(|place| place.tags.is_empty()).count();
+
+  let model = NeshanDataModel { tag_pool, items };
+  let json = serde_json::to_string_pretty(&model).map_err(|err| err.to_string())?;
+  fs::write(&args.out, json + "\n").map_err(|err| format!("{}: {err}", args.out.display()))?;
+  eprintln!(
+    "wrote {} places ({} tags, {untagged} untagged) to {}; dropped {duplicates} duplicates and \
+     {skipped} of unknown type",
+    model.items.len(),
+    model.tag_pool.len(),
+    args.out.display(),
+  );
+  Ok(())
+}
+
+fn main() -> ExitCode {
+  match run(Args::parse()) {
+    Ok(()) => ExitCode::SUCCESS,
+    Err(err) => {
+      eprintln!("error: {err}");
+      ExitCode::FAILURE
+    }
+  }
+}
@@ -0,0 +1,38 @@
+//! Helpers shared by the command line tools.
+
+use gardesh_core::catalog::MANIFEST_FILE;
+use gardesh_core::{CatalogManifest, FixtureMode, LlmConfig, LlmProvider, PlaceCatalog};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+/// Loads the data pack whose `manifest.json` is in `dir`.
+pub fn load_catalog(dir: &Path) -> Result<PlaceCatalog, String> {
+  let read = |file: &str| {
+    let path = dir.join(file);
+    fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))
+  };
+  let manifest = CatalogManifest::parse(&read(MANIFEST_FILE)?).map_err(|err| err.to_string())?;
+  PlaceCatalog::load(manifest, read).map_err(|err| err.to_string())
+}
+
+/// Reads an [`LlmConfig`] from a JSON file holding either the config itself or the app's settings
+/// with the config under `llm`.
+pub fn load_llm_config(path: &Path) -> Result<LlmConfig, String> {
+  let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+  let mut value = serde_json::from_str::<Value>(&text).map_err(|err| err.to_string())?;
+  if let Some(llm) = value.get_mut("llm") {
+    value = llm.take();
+  }
+  serde_json::from_value(value).map_err(|err| format!("{}: {err}", path.display()))
+}
+
+pub fn parse_provider(text: &str) -> Result<LlmProvider, String> {
+  serde_json::from_value(Value::String(text.to_lowercase()))
+    .map_err(|_| format!("unknown provider `{text}`; expected gemini, openai or ollama"))
+}
+
+pub fn parse_fixture_mode(text: &str) -> Result<FixtureMode, String> {
+  serde_json::from_value(Value::String(text.to_lowercase()))
+    .map_err(|_| format!("unknown fixture mode `{text}`; expected off, record or replay"))
+}
@@ -0,0 +1,65 @@
+use gardesh_core::cache::{cache_key, catalog_fingerprint};
+use gardesh_core::{AnalysisCache, CachePolicy, LlmConfig, PlaceCatalog, PromptAnalyses};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager};
+
+const CACHE_FILE: &str = "analyses.json";
+
+fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|f| f.as_secs()).unwrap_or_default()
+}
+
+/// `<app-cache>/analyses.json`: model analyses of earlier prompts, rewritten after every miss.
+pub struct CacheStore {
+  path: PathBuf,
+  cache: Mutex<AnalysisCache>,
+}
+
+impl CacheStore {
+  pub fn open(app: &AppHandle) -> tauri::Result<Self> {
+    let path = app.path().app_cache_dir()?.join(CACHE_FILE);
+    let cache = fs::read_to_string(&path)
+      .ok()
+      .and_then(|text| serde_json::from_str(&text).ok())
+      .unwrap_or_default();
+    Ok(Self { path, cache: Mutex::new(cache) })
+  }
+
+  pub fn get(
+    &self,
+    catalog: &PlaceCatalog,
+    config: &LlmConfig,
+    prompt: &str,
+    policy: &CachePolicy,
+  ) -> Option<PromptAnalyses> {
+    let fingerprint = catalog_fingerprint(catalog);
+    let mut cache = self.cache.lock().unwrap();
+    cache.check_catalog(&fingerprint);
+    cache.get(&cache_key(prompt, config, &fingerprint), policy, now())
+  }
+
+  pub fn put(
+    &self,
+    catalog: &PlaceCatalog,
+    config: &LlmConfig,
+    prompt: &str,
+    analyses: &PromptAnalyses,
+    policy: &CachePolicy,
+  ) {
+    let fingerprint = catalog_fingerprint(catalog);
+    let mut cache = self.cache.lock().unwrap();
+    cache.check_catalog(&fingerprint);
+    cache.insert(cache_key(prompt, config, &fingerprint), analyses.clone(), policy, now());
+    let written = self
+      .path
+      .parent()
+      .map_or(Ok(()), fs::create_dir_all)
+      .and_then(|_| fs::write(&self.path, serde_json::to_string(&*cache).unwrap()));
+    if let Err(err) = written {
+      println!("Could not write {}: {err}", self.path.display());
+    }
+  }
+}
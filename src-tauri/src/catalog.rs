@@ -0,0 +1,71 @@
+use gardesh_core::catalog::{CatalogError, CatalogManifest, PlaceCatalog, MANIFEST_FILE};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tauri::{AppHandle, Manager};
+
+/// Keeps the parsed catalog for the life of the app and swaps it when a new data pack lands.
+///
+/// Packs dropped into `<app-data>/catalog` take precedence over the one bundled with the app.
+pub struct CatalogStore {
+  dirs: Vec<PathBuf>,
+  current: RwLock<Arc<PlaceCatalog>>,
+}
+
+fn read_manifest(dir: &Path) -> Result<CatalogManifest, CatalogError> {
+  let path = dir.join(MANIFEST_FILE);
+  let text = fs::read_to_string(&path).map_err(|err| CatalogError::Read {
+    file: path.display().to_string(),
+    message: err.to_string(),
+  })?;
+  CatalogManifest::parse(&text)
+}
+
+fn load_dir(dir: &Path, manifest: CatalogManifest) -> Result<PlaceCatalog, CatalogError> {
+  PlaceCatalog::load(manifest, |file| fs::read_to_string(dir.join(file)).map_err(|f| f.to_string()))
+}
+
+impl CatalogStore {
+  pub fn open(app: &AppHandle) -> Result<Self, CatalogError> {
+    let dirs = [app.path().app_data_dir(), app.path().resource_dir()]
+      .into_iter()
+      .flatten()
+      .map(|dir| dir.join("catalog"))
+      .collect::<Vec<_>>();
+    let store = Self { dirs, current: RwLock::new(Arc::default()) };
+    store.reload()?;
+    Ok(store)
+  }
+
+  /// The first directory holding a readable manifest wins.
+  fn active_manifest(&self) -> Result<(&Path, CatalogManifest), CatalogError> {
+    let mut last_err = None;
+    for dir in &self.dirs {
+      match read_manifest(dir) {
+        Ok(manifest) => return Ok((dir, manifest)),
+        Err(err) => last_err = Some(err),
+      }
+    }
+    Err(last_err.unwrap_or_else(|| CatalogError::Read {
+      file: MANIFEST_FILE.to_string(),
+      message: "no catalog directory".to_string(),
+    }))
+  }
+
+  pub fn reload(&self) -> Result<Arc<PlaceCatalog>, CatalogError> {
+    let (dir, manifest) = self.active_manifest()?;
+    let catalog = Arc::new(load_dir(dir, manifest)?);
+    println!("Loaded catalog {} from {}", catalog.version, dir.display());
+    *self.current.write().unwrap() = catalog.clone();
+    Ok(catalog)
+  }
+
+  /// Returns the cached catalog, reloading it first if the manifest on disk has a new version.
+  pub fn current(&self) -> Result<Arc<PlaceCatalog>, CatalogError> {
+    let cached = self.current.read().unwrap().clone();
+    match self.active_manifest() {
+      Ok((_, manifest)) if manifest.version != cached.version => self.reload(),
+      _ => Ok(cached),
+    }
+  }
+}
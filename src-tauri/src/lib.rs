@@ -1,10 +1,9 @@
 #![allow(unused)]
+mod cache;
+mod catalog;
+mod settings;
+
 use enum_all_variants::AllVariants;
-use genai::{
-  chat::{ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, JsonSpec},
-  resolver::{AuthData, AuthResolver, AuthResolverFn},
-  Client, ClientConfig,
-};
 use kalosm::language::prompt_input;
 use std::{
   cmp::Ordering::Equal, collections::HashMap, env, fmt::Debug, path::PathBuf, str::FromStr,
@@ -21,65 +20,17 @@ use iter_tools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::time::Instant;
-use strum::{Display, EnumString};
-use strum::{VariantArray, VariantNames};
-
-#[derive(
-  Debug,
-  Serialize,
-  Deserialize,
-  PartialEq,
-  Eq,
-  Hash,
-  Copy,
-  Display,
-  Clone,
-  EnumString,
-  VariantArray,
-  VariantNames,
-)]
-enum PlaceType {
-  #[serde(rename = "موزه")]
-  #[strum(to_string = "موزه")]
-  Museum,
-  #[serde(rename = "مکان تاریخی")]
-  #[strum(to_string = "مکان تاریخی")]
-  Historical,
-  #[serde(rename = "رستوران")]
-  #[strum(to_string = "رستوران")]
-  Restaurant,
-}
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Location {
-  x: f64,
-  y: f64,
-}
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct PlaceInfo {
-  place_type: PlaceType,
-  tags: Vec<String>,
-}
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
-struct PromptAnalyse {
-  // entry_point: Option<Location>,
-  place_infos: Vec<PlaceInfo>,
-  total_count: Option<u32>,
-}
-
-fn distance_haversine(loc1: &Location, loc2: &Location) -> f64 {
-  let r = 6371e3; // Earth's radius in meters
-  let phi1 = loc1.y.to_radians();
-  let phi2 = loc2.y.to_radians();
-  let delta_phi = (loc2.y - loc1.y).to_radians();
-  let delta_lambda = (loc2.x - loc1.x).to_radians();
-
-  let a =
-    (delta_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
-  let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
-
-  r * c
-}
+use cache::CacheStore;
+use catalog::CatalogStore;
+use gardesh_core::offline::{analyze_offline, refine_offline};
+use gardesh_core::{
+  apply_edits, plan_checked, AnalyzeError, ChatMessage, CheckedPlan, LlmAnalyzer, LlmConfig,
+  NeshanDataModel, Place, PlaceCatalog, PlaceScoring, PlaceType, PlanEdit, PlanError, PlanStop,
+  PromptAnalyses, PromptAnalyzer,
+};
+use settings::{Settings, SettingsStore};
+use tauri::{Manager, State};
 
 // #[derive(Debug, Serialize, Deserialize, Clone)]
 // struct FinalResponse {
@@ -96,33 +47,10 @@ fn distance_haversine(loc1: &Location, loc2: &Location) -> f64 {
 //   names: Vec<String>,
 // }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct Place {
-  title: String,
-  category: String,
-  r#type: PlaceType,
-  region: String,
-  #[serde(default)]
-  neighbourhood: String,
-  location: Location,
-  #[serde(default)]
-  tags: Vec<String>,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
-struct Model {
-  tag_pool: Vec<String>,
-  items: Vec<Place>,
-}
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct PlaceScoringItems {
   items: Vec<PlaceScoring>,
 }
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct PlaceScoring {
-  place: Place,
-  score: usize,
-}
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct PromptClassification {
@@ -146,238 +74,154 @@ pub fn run() {
     //     .build(),
     // )
     .plugin(tauri_plugin_shell::init())
-    .invoke_handler(tauri::generate_handler![greet, answer])
+    .setup(|app| {
+      app.manage(CatalogStore::open(app.handle())?);
+      app.manage(SettingsStore::open(app.handle())?);
+      app.manage(CacheStore::open(app.handle())?);
+      Ok(())
+    })
+    .invoke_handler(tauri::generate_handler![
+      greet,
+      refine,
+      answer,
+      reload_catalog,
+      map_key,
+      save_settings
+    ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }
-const MODEL: &str = "gemini-2.0-flash-exp";
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
-async fn greet(name: String) -> Vec<Place> {
-  do_the_job(name).await
+async fn greet(
+  name: String,
+  catalog: State<'_, CatalogStore>,
+  settings: State<'_, SettingsStore>,
+  cache: State<'_, CacheStore>,
+) -> Result<CheckedPlan, PlanError> {
+  let catalog = catalog.current().map_err(|err| PlanError::Catalog { message: err.to_string() })?;
+  do_the_job(&catalog, &settings.current(), &cache, name).await
 }
 
-async fn do_the_job(name: String) -> Vec<Place> {
-  // use rand::{rngs::StdRng, Rng as _, SeedableRng};
-  // let mut rng: StdRng = StdRng::from_entropy();
-
-  // let random_points = (0..20000)
-  //   .map(|_| {
-  //     let lat = rng.gen_range(35.60..=35.80);
-  //     let lng = rng.gen_range(51.20..=51.50);
-  //     [lat, lng]
-  //   })
-  //   .collect::<Vec<_>>();
-  // use rstar::RTree;
-  // let tree = RTree::bulk_load(random_points);
-  // let n = Instant::now();
-  // let random_points = (0..10)
-  //   .map(|_| {
-  //     let lat = rng.gen_range(35.60..=35.80);
-  //     let lng = rng.gen_range(51.20..=51.50);
-  //     [lat, lng]
-  //   })
-  //   .for_each(|f| {
-  //     let nearest_neighbors = tree.nearest_neighbor_iter(&f).collect::<Vec<_>>();
-
-  //     println!("{nearest_neighbors:#?}");
-  //   });
-
-  // println!("Time Passed: {}", n.elapsed().as_millis());
-
-  // Lat: 35.60 - 35.80
-  // Long: 51.20 - 51.50
-  let neshan_history = serde_json::from_str::<Model>(include_str!(
-    "taged_items/neshan_history_results_unique_with_tags.json"
-  ))
-  .unwrap();
-  let neshan_museum = serde_json::from_str::<Model>(include_str!(
-    "taged_items/neshan_museum_results_unique_with_tags.json"
-  ))
-  .unwrap();
-  let neshan_restaurant = serde_json::from_str::<Model>(include_str!(
-    "taged_items/neshan_restaurant_results_unique_with_tags.json"
-  ))
-  .unwrap();
-
-  let system_prompt = format!("
-    درخواست کاربر را تجزیه تحلیل کن.
-    هر نوع مکان ذکر شده را شناسایی کن که یکی از این سه نوع است: موزه، رستوران، مکان تاریخی.
-    سپس برای هر مکان، از بین لیست تگ های زیر، مرتبط ترین موارد به درخواست کاربر را انتخاب کن.
-    اگر کاربر در درخواست خود تعداد مکان هایی که میخواهد ببیند را ذکر کرد، آن را هم در متغیر total_cont بیاور.
-    لیست تگ های مکان های تاریخی:[\n{}]\n
-    لیست تگ های موزه ها:[\n{}]\n
-    لیست تگ های رستوران ها:[\n{}]\n",
-    neshan_history.tag_pool.join("\n"),
-    neshan_museum.tag_pool.join("\n"),
-    neshan_restaurant.tag_pool.join("\n"),
-  );
+/// Applies a follow-up prompt to the session's current plan.
+#[tauri::command]
+async fn refine(
+  prompt: String,
+  history: Vec<ChatMessage>,
+  stops: Vec<PlanStop>,
+  catalog: State<'_, CatalogStore>,
+  settings: State<'_, SettingsStore>,
+) -> Result<Vec<PlanStop>, PlanError> {
+  let catalog = catalog.current().map_err(|err| PlanError::Catalog { message: err.to_string() })?;
+  let edits = interpret(&catalog, &settings.current().llm, &history, &stops, &prompt)
+    .await
+    .map_err(|err| PlanError::Analyze { message: err.to_string() })?;
+  println!("Edits for {prompt:?}: {edits:?}");
+  apply_edits(&catalog, &stops, &edits)
+}
 
-  // -- Build the new client with this client_config
-  let client = Client::builder()
-    .with_config(ClientConfig::default().with_chat_options(
-      ChatOptions::default().with_response_format(ChatResponseFormat::JsonSpec(JsonSpec::new(
-        "items",
-        json!(
-          {
-            "type": "object",
-            "properties": {
-              "place_infos": {
-                "type": "array",
-                "items": {
-                  "type": "object",
-                  "properties": {
-                    "tags": {
-                      "type": "array",
-                      "items": {
-                        "type": "string"
-                      }
-                    },
-                    "place_type": {
-                      "type": "string",
-                      "enum": [
-                        "موزه",
-                        "مکان تاریخی",
-                        "رستوران"
-                      ]
-                    }
-                  }
-                }
-              },
-              "total_count": {
-                "type": "integer"
-              }
-            },
-            "required": [
-              "place_infos"
-            ]
-          }
-        ),
-      ))), // .with_temperature(0.0)
-           // .with_top_p(0.99),
-    ))
-    .build();
-  // -- Build the chat request
-  let chat_req =
-    ChatRequest::new(vec![ChatMessage::system(system_prompt), ChatMessage::user(name)]);
-  println!("Question:\n{:#?}", chat_req);
+/// Re-reads the data pack even if its version did not change; returns the loaded version.
+#[tauri::command]
+fn reload_catalog(catalog: State<'_, CatalogStore>) -> Result<String, String> {
+  catalog.reload().map(|f| f.version.clone()).map_err(|err| err.to_string())
+}
 
-  // -- Build the chat request options (used per execution chat)
-  let chat_res = client
-    .exec_chat(MODEL, chat_req.clone(), Some(&ChatOptions::default().with_max_tokens(1000)))
-    .await
-    .unwrap();
-  println!("{:#?}", chat_res);
-  ///////
+/// The Neshan key the page needs to draw the map; fetched at runtime so it is not compiled in.
+#[tauri::command]
+fn map_key(settings: State<'_, SettingsStore>) -> String {
+  settings.current().neshan_api_key
+}
 
-  use vec_embed_store::{EmbeddingEngineOptions, EmbeddingsDb, SimilaritySearch, TextChunk};
+#[tauri::command]
+fn save_settings(new_settings: Settings, settings: State<'_, SettingsStore>) -> Result<(), String> {
+  settings.save(new_settings)
+}
 
-  let prompt_analyse =
-    serde_json::from_str::<PromptAnalyse>(chat_res.content_text_as_str().unwrap()).unwrap();
-  println!("🟣🟣🟣\n{prompt_analyse:#?}\n🟣🟣🟣");
-  // let neshan_history = serde_json::from_str::<Model>(include_str!(
-  //   "taged_items/neshan_history_results_unique_with_tags.json"
-  // ))
-  // .unwrap();
-  // let neshan_museum = serde_json::from_str::<Model>(include_str!(
-  //   "taged_items/neshan_museum_results_unique_with_tags.json"
-  // ))
-  // .unwrap();
-  // let neshan_restaurant = serde_json::from_str::<Model>(include_str!(
-  //   "taged_items/neshan_restaurant_results_unique_with_tags.json"
-  // ))
-  // .unwrap();
-  // let prompt_analyse = PromptAnalyse {
-  //   place_infos: vec![
-  //     PlaceInfo {
-  //       place_type: PlaceType::Historical,
-  //       tags: vec!["دوره صفویه".to_string(), "دوره تاریخی نامشخص".to_string()],
-  //     },
-  //     PlaceInfo {
-  //       place_type: PlaceType::Restaurant, tags: vec!["دیزی سرای سید".to_string()]
-  //     },
-  //     PlaceInfo { place_type: PlaceType::Museum, tags: vec!["ساعت".to_string()] },
-  //   ],
-  //   total_count: Some(2),
-  // };
-  let mut res = prompt_analyse
-    .place_infos
-    .into_iter()
-    .map(|info| {
-      // println!("{info:#?}");
-      match info.place_type {
-        PlaceType::Museum => neshan_museum
-          .items
-          .iter()
-          .map(|place| PlaceScoring {
-            score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-            place: place.clone(),
-          })
-          .filter(|place_scoring| place_scoring.score > 0)
-          .collect::<Vec<_>>(),
-        PlaceType::Historical => neshan_history
-          .items
-          .iter()
-          .map(|place| PlaceScoring {
-            score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-            place: place.clone(),
-          })
-          .filter(|place_scoring| place_scoring.score > 0)
-          .collect::<Vec<_>>(),
-        PlaceType::Restaurant => neshan_restaurant
-          .items
-          .iter()
-          .map(|place| PlaceScoring {
-            score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-            place: place.clone(),
-          })
-          .filter(|place_scoring| place_scoring.score > 0)
-          .collect::<Vec<_>>(),
+/// Analyzes with the configured model, or offline when it has no key or the request fails.
+///
+/// The model's answers are cached; the model's error is only reported when the offline analysis
+/// finds nothing either.
+async fn analyze(
+  catalog: &PlaceCatalog,
+  settings: &Settings,
+  cache: &CacheStore,
+  prompt: &str,
+) -> Result<PromptAnalyses, AnalyzeError> {
+  let config = &settings.llm;
+  if !config.has_credentials() {
+    return Ok(analyze_offline(catalog, prompt));
+  }
+  let analyzer = LlmAnalyzer::new(config.clone())?;
+  let model = analyzer.name();
+  if let Some(analyses) = cache.get(catalog, config, prompt, &settings.cache) {
+    println!("Cached analysis from {model}");
+    return Ok(analyses);
+  }
+  println!("Analyzing with {model}");
+  match analyzer.analyze_checked(catalog, prompt).await {
+    Ok(checked) => {
+      println!("{}", checked.prompt_size);
+      if !checked.report.is_clean() {
+        println!("Corrected the analysis (re-asked: {}):\n{}", checked.reasked, checked.report);
       }
-    })
-    .filter(|palce_scoring| palce_scoring.len() > 0)
-    .collect::<Vec<_>>();
-  // println!("{res:#?}");
-  res
-    .into_iter()
-    .inspect(|f| println!("🟡{f:#?}"))
-    .multi_cartesian_product()
-    .inspect(|f| println!("🙏{f:#?}"))
-    .sorted_by(|a, b| {
-      let max_distance = 20.0; // km
-
-      let dist_a = (0..a.len())
-        .map(|i| distance_haversine(&a[i].place.location, &a[(i + 1) % a.len()].place.location))
-        .sum::<f64>();
-      let dist_b = (0..b.len())
-        .map(|i| distance_haversine(&b[i].place.location, &b[(i + 1) % b.len()].place.location))
-        .sum::<f64>();
+      cache.put(catalog, config, prompt, &checked.analyses, &settings.cache);
+      Ok(checked.analyses)
+    }
+    Err(err) => {
+      println!("{err}; falling back to offline analysis");
+      Some(analyze_offline(catalog, prompt)).filter(|f| !f.place_infos.is_empty()).ok_or(err)
+    }
+  }
+}
 
-      // Normalize distances to 0-1 scale
-      let norm_dist_a = dist_a / max_distance;
-      let norm_dist_b = dist_b / max_distance;
+/// Reads a follow-up as plan edits, falling back to the offline rules like [`analyze`] does.
+async fn interpret(
+  catalog: &PlaceCatalog,
+  config: &LlmConfig,
+  history: &[ChatMessage],
+  stops: &[PlanStop],
+  prompt: &str,
+) -> Result<Vec<PlanEdit>, AnalyzeError> {
+  if !config.has_credentials() {
+    return Ok(refine_offline(catalog, stops, prompt));
+  }
+  let analyzer = LlmAnalyzer::new(config.clone())?;
+  match analyzer.refine_checked(catalog, history, stops, prompt).await {
+    Ok(checked) => {
+      if !checked.report.is_clean() {
+        println!("Corrected the edits (re-asked: {}):\n{}", checked.reasked, checked.report);
+      }
+      Ok(checked.edits)
+    }
+    Err(err) => {
+      println!("{err}; falling back to offline rules");
+      Some(refine_offline(catalog, stops, prompt)).filter(|f| !f.is_empty()).ok_or(err)
+    }
+  }
+}
 
-      // Normalize scores to 0-1 scale (assuming higher score is better)
-      let score_a =
-        a.iter().map(|item| (item.score - 1) as f64 / 3.0).sum::<f64>() / a.len() as f64;
-      let score_b =
-        b.iter().map(|item| (item.score - 1) as f64 / 3.0).sum::<f64>() / b.len() as f64;
+async fn do_the_job(
+  catalog: &PlaceCatalog,
+  settings: &Settings,
+  cache: &CacheStore,
+  name: String,
+) -> Result<CheckedPlan, PlanError> {
+  // Lat: 35.60 - 35.80
+  // Long: 51.20 - 51.50
+  use vec_embed_store::{EmbeddingEngineOptions, EmbeddingsDb, SimilaritySearch, TextChunk};
 
-      // Combine normalized distance and score. Here we use subtraction because lower distance and higher score are better.
-      // If you want to make distance and score equally important, you might adjust the weights.
-      let combined_a = (0.7 * norm_dist_a) - (0.3 * score_a);
-      let combined_b = (0.7 * norm_dist_b) - (0.3 * score_b);
-      combined_a.partial_cmp(&combined_b).unwrap_or(Equal)
-    })
-    .next()
-    .unwrap()
-    .into_iter()
-    .map(|place_scoring| place_scoring.place)
-    .collect::<Vec<Place>>()
-  // panic!("HEEEEY");
-  // println!("✅✅✅✅\n{final_places:#?}\n✅✅✅✅");
-  // final_places
+  let prompt_analyse = analyze(catalog, settings, cache, &name)
+    .await
+    .map_err(|err| PlanError::Analyze { message: err.to_string() })?;
+  let checked = plan_checked(catalog, &prompt_analyse)?;
+  if let Some(start) = &checked.start {
+    println!("Planning from {}", start.name);
+  }
+  for unmet in &checked.unmet {
+    println!("Not honored: {unmet}");
+  }
+  Ok(checked)
 }
 
 #[derive(Debug, Serialize)]
@@ -0,0 +1,99 @@
+use gardesh_core::prefilter::DEFAULT_PROMPT_TAGS;
+use gardesh_core::{CachePolicy, FixtureMode, LlmConfig, LlmProvider};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use tauri::{AppHandle, Manager};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Secrets and provider choices; they live only in the backend and never in the page's bundle.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Settings {
+  #[serde(default)]
+  pub llm: LlmConfig,
+  #[serde(default)]
+  pub neshan_api_key: String,
+  /// Limits of the cache of model analyses.
+  #[serde(default)]
+  pub cache: CachePolicy,
+}
+
+impl Settings {
+  /// First-run values from the environment (and `.env` in development): `LLM_PROVIDER`
+  /// (`gemini`, `openai` or `ollama`), `LLM_MODEL`, `LLM_BASE_URL`, `LLM_PROXY`,
+  /// `LLM_TIMEOUT_SECS`, `LLM_REASK` (`1` or `true`), `LLM_FIXTURES` (`record` or `replay`),
+  /// `LLM_FIXTURE_DIR`, `LLM_PROMPT_TAGS` (a number, or `all`), `LLM_API_KEY` falling back to the
+  /// provider's usual variable, and `NESHAN_API_KEY`.
+  fn from_env() -> Self {
+    let provider = env::var("LLM_PROVIDER")
+      .ok()
+      .and_then(|f| {
+        serde_json::from_value::<LlmProvider>(Value::String(f.trim().to_lowercase())).ok()
+      })
+      .unwrap_or_default();
+    let llm = LlmConfig {
+      provider,
+      // Left empty when unset, so `LlmConfig::model` and `LlmConfig::api_key` pick the
+      // provider's defaults.
+      model: env::var("LLM_MODEL").unwrap_or_default(),
+      api_key: env::var("LLM_API_KEY").unwrap_or_default(),
+      base_url: env::var("LLM_BASE_URL").ok(),
+      proxy: env::var("LLM_PROXY").ok(),
+      timeout_secs: env::var("LLM_TIMEOUT_SECS").ok().and_then(|f| f.trim().parse().ok()),
+      headers: Default::default(),
+      reask_on_invalid: env::var("LLM_REASK").is_ok_and(|f| matches!(f.trim(), "1" | "true")),
+      fixture_mode: env::var("LLM_FIXTURES")
+        .ok()
+        .and_then(|f| {
+          serde_json::from_value::<FixtureMode>(Value::String(f.trim().to_lowercase())).ok()
+        })
+        .unwrap_or_default(),
+      fixture_dir: env::var("LLM_FIXTURE_DIR").ok(),
+      prompt_tags: env::var("LLM_PROMPT_TAGS")
+        .map_or(Some(DEFAULT_PROMPT_TAGS), |f| f.trim().parse().ok()),
+    };
+    Self {
+      llm,
+      neshan_api_key: env::var("NESHAN_API_KEY").unwrap_or_default(),
+      cache: CachePolicy::default(),
+    }
+  }
+}
+
+/// `<app-config>/settings.json`, read once at startup and rewritten on every save.
+pub struct SettingsStore {
+  path: PathBuf,
+  current: RwLock<Settings>,
+}
+
+impl SettingsStore {
+  pub fn open(app: &AppHandle) -> tauri::Result<Self> {
+    let path = app.path().app_config_dir()?.join(SETTINGS_FILE);
+    let settings = match fs::read_to_string(&path) {
+      Ok(text) => serde_json::from_str(&text).unwrap_or_else(|err| {
+        println!("Ignoring unreadable {}: {err}", path.display());
+        Settings::from_env()
+      }),
+      Err(_) => Settings::from_env(),
+    };
+    Ok(Self { path, current: RwLock::new(settings) })
+  }
+
+  pub fn current(&self) -> Settings {
+    self.current.read().unwrap().clone()
+  }
+
+  pub fn save(&self, settings: Settings) -> Result<(), String> {
+    if let Some(dir) = self.path.parent() {
+      fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+    }
+    let text = serde_json::to_string_pretty(&settings).map_err(|err| err.to_string())?;
+    fs::write(&self.path, text).map_err(|err| err.to_string())?;
+    *self.current.write().unwrap() = settings;
+    Ok(())
+  }
+}
@@ -0,0 +1,68 @@
+use gardesh_core::{ChatMessage, CheckedPlan, PlanError, PlanStop};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+use web_sys::js_sys::Reflect;
+
+use crate::error::AskError;
+
+#[wasm_bindgen]
+extern "C" {
+  #[wasm_bindgen(js_namespace = ["window", "__TAURI__", "core"], catch)]
+  async fn invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// True inside the desktop app; a plain browser has no backend to invoke.
+pub fn is_tauri() -> bool {
+  web_sys::window()
+    .is_some_and(|window| Reflect::has(&window, &"__TAURI__".into()).unwrap_or(false))
+}
+
+#[derive(Serialize)]
+struct GreetArgs<'a> {
+  name: &'a str,
+}
+
+#[derive(Serialize)]
+struct RefineArgs<'a> {
+  prompt: &'a str,
+  history: &'a [ChatMessage],
+  stops: &'a [PlanStop],
+}
+
+/// Analyzes and plans `prompt` in the backend, where the model's API key is kept.
+pub async fn plan(prompt: &str) -> Result<CheckedPlan, AskError> {
+  invoke_plan("greet", &GreetArgs { name: prompt }).await
+}
+
+/// Applies the follow-up `prompt` to `stops` in the backend.
+pub async fn refine(
+  prompt: &str,
+  history: &[ChatMessage],
+  stops: &[PlanStop],
+) -> Result<Vec<PlanStop>, AskError> {
+  invoke_plan("refine", &RefineArgs { prompt, history, stops }).await
+}
+
+/// Calls a command answering with a plan, or with a [`PlanError`] on failure.
+async fn invoke_plan<T: DeserializeOwned>(cmd: &str, args: &impl Serialize) -> Result<T, AskError> {
+  let args = serde_wasm_bindgen::to_value(args)
+    .map_err(|err| AskError::Invoke { message: err.to_string() })?;
+  match invoke(cmd, args).await {
+    Ok(plan) => serde_wasm_bindgen::from_value(plan)
+      .map_err(|err| AskError::Invoke { message: err.to_string() }),
+    Err(err) => Err(match serde_wasm_bindgen::from_value::<PlanError>(err.clone()) {
+      Ok(err) => AskError::Plan(err),
+      Err(_) => AskError::Invoke { message: format!("{err:?}") },
+    }),
+  }
+}
+
+/// The Neshan map key from the backend settings, or from localStorage `neshan_api_key` in a
+/// plain browser.
+pub async fn map_key() -> Option<String> {
+  if is_tauri() {
+    return invoke("map_key", JsValue::NULL).await.ok()?.as_string().filter(|f| !f.is_empty());
+  }
+  web_sys::window()?.local_storage().ok()??.get_item("neshan_api_key").ok()?
+}
@@ -0,0 +1,59 @@
+use gardesh_core::catalog::{CatalogError, CatalogManifest, PlaceCatalog, MANIFEST_FILE};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::Response;
+
+/// Where trunk copies `public/catalog`; data packs dropped in here are picked up on the next prompt.
+const CATALOG_URL: &str = "public/catalog";
+
+thread_local! {
+  static CATALOG: RefCell<Option<Rc<PlaceCatalog>>> = const { RefCell::new(None) };
+}
+
+async fn fetch_text(url: &str) -> Result<String, String> {
+  let window = web_sys::window().ok_or("no window")?;
+  let response: Response = JsFuture::from(window.fetch_with_str(url))
+    .await
+    .and_then(|response| response.dyn_into())
+    .map_err(|err| format!("{err:?}"))?;
+  if !response.ok() {
+    return Err(format!("HTTP {}", response.status()));
+  }
+  let text = JsFuture::from(response.text().map_err(|err| format!("{err:?}"))?)
+    .await
+    .map_err(|err| format!("{err:?}"))?;
+  text.as_string().ok_or_else(|| "response is not text".to_string())
+}
+
+/// The last loaded catalog, for synchronous callers such as map effects.
+pub fn cached_catalog() -> Option<Rc<PlaceCatalog>> {
+  CATALOG.with_borrow(Option::clone)
+}
+
+/// Returns the cached catalog, reloading it only when the deployed manifest version changed.
+pub async fn load_catalog() -> Result<Rc<PlaceCatalog>, CatalogError> {
+  let manifest_text = fetch_text(&format!("{CATALOG_URL}/{MANIFEST_FILE}"))
+    .await
+    .map_err(|message| CatalogError::Read { file: MANIFEST_FILE.to_string(), message })?;
+  let manifest = CatalogManifest::parse(&manifest_text)?;
+  if let Some(catalog) =
+    CATALOG.with_borrow(|cached| cached.as_ref().filter(|f| f.version == manifest.version).cloned())
+  {
+    return Ok(catalog);
+  }
+
+  // `PlaceCatalog::load` reads synchronously, so fetch every pack up front.
+  let mut texts = HashMap::new();
+  for category in &manifest.categories {
+    texts
+      .insert(category.file.clone(), fetch_text(&format!("{CATALOG_URL}/{}", category.file)).await);
+  }
+  let catalog = Rc::new(PlaceCatalog::load(manifest, |file| {
+    texts.remove(file).unwrap_or_else(|| Err("not fetched".to_string()))
+  })?);
+  CATALOG.set(Some(catalog.clone()));
+  Ok(catalog)
+}
@@ -1,20 +1,22 @@
 use crate::components::session_content::SessionContent;
 use crate::components::sidebar::Sidebar;
 
+use crate::backend::map_key;
+use crate::catalog::{cached_catalog, load_catalog};
 use crate::{
-  Map, Marker, Session, SessionStoreFields, State, StateStoreFields, SuggestionStoreFields,
+  Map, Marker, PlaceId, PlaceType, Session, SessionStoreFields, State, StateStoreFields,
+  SuggestionStoreFields,
 };
 use chrono::Local;
-use dotenvy_macro::dotenv;
 use iter_tools::Itertools;
 use leptos::leptos_dom::logging::console_log;
 use leptos::tachys::html::node_ref::{self, node_ref};
-use leptos::{either::Either, prelude::*};
+use leptos::{either::Either, prelude::*, task::spawn_local};
 use reactive_stores::{Field, Store, StoreFieldIterator};
 use wasm_bindgen::prelude::*;
 use web_sys::js_sys::{Array, Object, Reflect};
 
-fn config_map(options: &Object) {
+fn config_map(options: &Object, map_key: &str) {
   Reflect::set(options, &JsValue::from_str("mapType"), &JsValue::from_str("neshanVector")).unwrap();
   Reflect::set(options, &JsValue::from_str("container"), &JsValue::from_str("map")).unwrap();
   Reflect::set(options, &JsValue::from_str("zoom"), &JsValue::from_f64(10.0)).unwrap();
@@ -28,12 +30,7 @@ fn config_map(options: &Object) {
   Reflect::set(options, &JsValue::from_str("minZoom"), &JsValue::from_f64(2.0)).unwrap();
   Reflect::set(options, &JsValue::from_str("maxZoom"), &JsValue::from_f64(21.0)).unwrap();
   Reflect::set(options, &JsValue::from_str("trackResize"), &JsValue::from_bool(true)).unwrap();
-  Reflect::set(
-    options,
-    &JsValue::from_str("mapKey"),
-    &JsValue::from_str(dotenv!("NESHAN_API_KEY")),
-  )
-  .unwrap();
+  Reflect::set(options, &JsValue::from_str("mapKey"), &JsValue::from_str(map_key)).unwrap();
   Reflect::set(options, &JsValue::from_str("poi"), &JsValue::from_bool(false)).unwrap();
   Reflect::set(options, &JsValue::from_str("traffic"), &JsValue::from_bool(false)).unwrap();
   let map_controller_options = Object::new();
@@ -53,42 +50,89 @@ fn config_map(options: &Object) {
   .unwrap();
 }
 
+/// A marker showing the category's registry icon, or the default pin for unknown categories.
+fn category_marker(place_type: &PlaceType) -> Marker {
+  let icon = cached_catalog().and_then(|f| f.category(place_type).map(|f| f.def.icon.clone()));
+  let element = icon.zip(web_sys::window().and_then(|f| f.document())).and_then(|(icon, doc)| {
+    let element = doc.create_element("i").ok()?;
+    element.set_class_name(&format!("marker fa {icon}"));
+    Some(element)
+  });
+  match element {
+    Some(element) => {
+      let options = Object::new();
+      Reflect::set(&options, &JsValue::from_str("element"), &element).unwrap();
+      Marker::newMarkerWithOptions(&options)
+    }
+    None => Marker::newMarker(),
+  }
+}
+
 #[component]
 pub fn App() -> impl IntoView {
   let state = Store::new(State::default());
   let selected_session: RwSignal<Option<Field<Session>>> = RwSignal::new(None);
-  let map_options = Object::new();
-  config_map(&map_options);
-  let markers = StoredValue::new_local(Vec::<Marker>::new());
+  // The key comes from the backend at runtime; the map is created once it has arrived.
+  let map_options: RwSignal<Option<Object>, LocalStorage> = RwSignal::new_local(None);
+  spawn_local(async move {
+    match map_key().await {
+      Some(key) => {
+        let options = Object::new();
+        config_map(&options, &key);
+        map_options.set(Some(options));
+      }
+      None => console_log("no Neshan map key configured"),
+    }
+  });
+  // Marker icons come from the registry, which the desktop build otherwise never fetches.
+  spawn_local(async move {
+    if let Err(err) = load_catalog().await {
+      console_log(&format!("{err}"));
+    }
+  });
+  let markers = StoredValue::new_local(Vec::<(PlaceId, Marker)>::new());
   let map_ref: RwSignal<Option<Map>, LocalStorage> = RwSignal::new_local(None);
 
   Effect::new(move |_| {
-    let map_options = map_options.clone();
     // request_animation_frame(move || {
     match (selected_session.try_get().flatten(), map_ref.try_get().flatten()) {
-      (None, Some(_)) => map_ref.set(None),
-      (Some(_), None) => map_ref.set(Some(Map::newMap(&JsValue::from(map_options)))),
+      (None, Some(_)) => {
+        markers.update_value(|markers| markers.drain(..).for_each(|(_, marker)| marker.remove()));
+        map_ref.set(None)
+      }
+      (Some(_), None) => {
+        if let Some(map_options) = map_options.get() {
+          map_ref.set(Some(Map::newMap(&JsValue::from(map_options))))
+        }
+      }
       (Some(selected_session), Some(map_ref)) => {
-        markers.get_value().iter().for_each(Marker::remove);
-        markers.set_value(
-          selected_session
-            .read()
-            .suggestions
-            .iter()
-            .map(|sg| {
-              // let (x, y) = sg.selected_place().with(|f| (f.location.x, f.location.y));
-              Marker::newMarker().setLngLat(&JsValue::from(Array::of2(
-                &JsValue::from_f64(sg.selected_place.location.x),
-                &JsValue::from_f64(sg.selected_place.location.y),
-              )))
-            })
-            .inspect(|marker| {
-              // let rr = web_sys::Element::from(JsValue::from(marker));
-              // web_sys::Element::set_class_name(&rr, "c");
-              marker.addTo(&map_ref);
-            })
-            .collect_vec(),
-        );
+        let place_ids = selected_session.read().place_ids();
+        // Keep markers of places still on the plan and only touch the ones that changed.
+        markers.update_value(|markers| {
+          markers.retain(|(id, marker)| {
+            let keep = place_ids.contains(id);
+            if !keep {
+              marker.remove();
+            }
+            keep
+          })
+        });
+        let new_markers = selected_session
+          .read()
+          .suggestions
+          .iter()
+          .map(|sg| &sg.selected_place)
+          .filter(|place| markers.with_value(|f| f.iter().all(|(id, _)| *id != place.id)))
+          .map(|place| {
+            let marker = category_marker(&place.r#type).setLngLat(&JsValue::from(Array::of2(
+              &JsValue::from_f64(place.location.x),
+              &JsValue::from_f64(place.location.y),
+            )));
+            marker.addTo(&map_ref);
+            (place.id.clone(), marker)
+          })
+          .collect_vec();
+        markers.update_value(|markers| markers.extend(new_markers));
       }
       (None, None) => {}
     }
@@ -99,6 +143,9 @@ pub fn App() -> impl IntoView {
   state.sessions().write().push(Session {
     date_created: Local::now(),
     suggestions: Vec::new(),
+    failure: None,
+    history: Vec::new(),
+    unmet: Vec::new(),
     title: "جلسه ".to_string(),
   });
   selected_session.set(state.sessions().into_iter().next().map(Into::into));
@@ -8,6 +8,12 @@ pub fn PlaceCard(#[prop(into)] place: Field<Place>) -> impl IntoView {
   view! {
     <div>
       <h2>{move || place.read().title.clone()}</h2>
+      <Show when=move || !place.read().address.is_empty()>
+        <p>
+          <strong>"Address:"</strong>
+          {move || place.read().address.clone()}
+        </p>
+      </Show>
       <p>
         <strong>"Category:"</strong>
         {move || place.read().category.clone()}
@@ -1,8 +1,15 @@
+use gardesh_core::conversation::describe_plan;
+use gardesh_core::geojson::itinerary_to_geojson;
+use gardesh_core::gpx::itinerary_to_gpx;
+use gardesh_core::kml::itinerary_to_kml;
+use gardesh_core::{ChatMessage, ChatRole, CheckedPlan};
+use leptos::leptos_dom::logging::console_log;
 use leptos::{prelude::*, task::spawn_local};
 use reactive_stores::Field;
 
 use crate::{
-  ask_ai, components::suggesions::Suggestions, Session, SessionStoreFields, State, StateStoreFields,
+  ask_ai, components::suggesions::Suggestions, download::download, error::AskFailure, Session,
+  SessionStoreFields, State, StateStoreFields, Suggestion,
 };
 
 #[component]
@@ -10,19 +17,114 @@ pub fn SessionContent(
   #[prop(into)] state: Field<State>,
   #[prop(into)] session: Field<Session>,
 ) -> impl IntoView {
-  let answer = move |_| {
+  let run = move |prompt: String| {
     spawn_local(async move {
       state.answering().set(true);
-      let answer = ask_ai(state.prompt_text().get()).await;
+      let (history, plan) = session.with(|f| (f.history.clone(), f.plan()));
+      let follow_up = !plan.is_empty();
+      let answer = ask_ai(prompt.clone(), history, plan).await;
       state.answering().set(false);
       //   console_log(&format!("{:#?}", answer.clone()));
-      session.suggestions().set(answer);
+      match answer {
+        Ok(CheckedPlan { stops, unmet, .. }) => {
+          session.failure().set(None);
+          // A follow-up edits the plan, so what its prompt could not honor still holds.
+          if !follow_up {
+            session.unmet().set(unmet);
+          }
+          session.history().update(|history| {
+            // A fresh plan starts a new conversation.
+            if !follow_up {
+              history.clear();
+            }
+            history.push(ChatMessage::user(prompt));
+            history.push(ChatMessage::assistant(describe_plan(&stops)));
+          });
+          session.suggestions().set(stops.into_iter().map(Suggestion::from).collect());
+        }
+        Err(err) => {
+          console_log(&format!("ask_ai failed for prompt {prompt:?}: {err}"));
+          session.failure().set(Some(AskFailure::new(prompt, &err)));
+        }
+      }
     });
   };
+  let answer = move |_| run(state.prompt_text().get());
+  let retry = move |_| {
+    if let Some(failure) = session.failure().get() {
+      run(failure.prompt);
+    }
+  };
+  let export = move |format: &'static str| {
+    let (title, stops) = session.with(|f| (f.title.trim().to_string(), f.stops()));
+    let (mime, contents) = match format {
+      "gpx" => ("application/gpx+xml", itinerary_to_gpx(&title, &stops)),
+      "kml" => ("application/vnd.google-earth.kml+xml", itinerary_to_kml(&title, &stops)),
+      _ => ("application/geo+json", itinerary_to_geojson(&stops).to_string()),
+    };
+    if let Err(err) = download(&format!("itinerary.{format}"), mime, &contents) {
+      console_log(&format!("{format} export failed: {err:?}"));
+    }
+  };
   view! {
     <div>
       <div id="map"></div>
+      <Show when=move || !session.suggestions().read().is_empty()>
+        <div class="export">
+          <button on:click=move |_| export("geojson") class="fa fa-download">
+            " GeoJSON"
+          </button>
+          <button on:click=move |_| export("gpx") class="fa fa-download">
+            " GPX"
+          </button>
+          <button on:click=move |_| export("kml") class="fa fa-download">
+            " KML"
+          </button>
+        </div>
+      </Show>
+      <ol class="history">
+        {move || {
+          session
+            .history()
+            .get()
+            .into_iter()
+            .filter(|message| message.role == ChatRole::User)
+            .map(|message| view! { <li>{message.content}</li> })
+            .collect_view()
+        }}
+      </ol>
+      <Show when=move || !session.unmet().read().is_empty()>
+        <ul class="unmet">
+          {move || {
+            session
+              .unmet()
+              .get()
+              .into_iter()
+              .map(|unmet| view! { <li title=unmet.to_string()>{unmet.message()}</li> })
+              .collect_view()
+          }}
+        </ul>
+      </Show>
       <Suggestions session {..} class="suggestions" />
+      {move || {
+        session
+          .failure()
+          .get()
+          .map(|failure| {
+            view! {
+              <div class="failure" title=failure.detail>
+                <span>{failure.summary}</span>
+                <button
+                  on:click=retry
+                  class="fa fa-refresh"
+                  disabled=move || state.answering().get()
+                >
+                  " تلاش دوباره"
+                </button>
+              </div>
+            }
+          })
+      }}
       <div class="bottom_bar">
         <textarea
           class="prompt"
@@ -18,6 +18,9 @@ pub fn Sidebar(
         // selected_suggestion: None,
         date_created: Local::now(),
         suggestions: Vec::new(),
+        failure: None,
+        history: Vec::new(),
+        unmet: Vec::new(),
         title: "جلسه ".to_string(),
       },
     );
@@ -0,0 +1,18 @@
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::js_sys::Array;
+use web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+
+/// Saves `contents` as a file through a temporary object URL.
+pub fn download(file_name: &str, mime: &str, contents: &str) -> Result<(), JsValue> {
+  let options = BlobPropertyBag::new();
+  options.set_type(mime);
+  let blob =
+    Blob::new_with_str_sequence_and_options(&Array::of1(&JsValue::from_str(contents)), &options)?;
+  let url = Url::create_object_url_with_blob(&blob)?;
+  let document = web_sys::window().and_then(|f| f.document()).ok_or("no document")?;
+  let anchor = document.create_element("a")?.dyn_into::<HtmlAnchorElement>()?;
+  anchor.set_href(&url);
+  anchor.set_download(file_name);
+  anchor.click();
+  Url::revoke_object_url(&url)
+}
@@ -0,0 +1,45 @@
+use derive_more::{Display, Error, From};
+use gardesh_core::{CatalogError, PlanError};
+
+/// Why a prompt produced no plan; each variant is one stage of [`crate::ask_ai`].
+#[derive(Debug, Display, Error, From)]
+pub enum AskError {
+  #[display("catalog unavailable: {_0}")]
+  Catalog(CatalogError),
+  #[display("{_0}")]
+  Plan(PlanError),
+  #[display("backend call failed: {message}")]
+  #[from(ignore)]
+  Invoke { message: String },
+}
+
+impl AskError {
+  /// One-line Persian explanation shown in the session.
+  pub fn summary(&self) -> &'static str {
+    match self {
+      AskError::Catalog(_) | AskError::Plan(PlanError::Catalog { .. }) => {
+        "بارگذاری فهرست مکان ها ممکن نشد."
+      }
+      AskError::Plan(PlanError::Analyze { .. }) => "تحلیل درخواست با خطا مواجه شد.",
+      AskError::Plan(PlanError::NothingRequested) => {
+        "نوع مکانی در درخواست پیدا نشد؛ مثلا بنویسید «یک موزه و یک رستوران»."
+      }
+      AskError::Plan(PlanError::NoMatch) => "مکانی با این ویژگی ها پیدا نشد.",
+      AskError::Invoke { .. } => "ارتباط با برنامه برقرار نشد.",
+    }
+  }
+}
+
+/// The last prompt of a session that failed, kept so it can be retried as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AskFailure {
+  pub prompt: String,
+  pub summary: String,
+  pub detail: String,
+}
+
+impl AskFailure {
+  pub fn new(prompt: String, error: &AskError) -> Self {
+    Self { prompt, summary: error.summary().to_string(), detail: error.to_string() }
+  }
+}
@@ -1,114 +1,29 @@
+pub mod backend;
+pub mod catalog;
 pub mod components;
+pub mod download;
+pub mod error;
 
 use better_default::Default;
-use dotenvy_macro::dotenv;
-use genai::adapter::AdapterKind;
-use genai::chat::{ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, JsonSpec};
-use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
-use genai::{Client, ClientConfig, ModelIden, ServiceTarget};
-use iter_tools::Itertools;
 use leptos::leptos_dom::logging::console_log;
 use reactive_stores::{Field, Store};
-use serde_json::json;
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
-use std::iter::repeat;
-use strum::{Display, EnumString, VariantArray};
 use wasm_bindgen::prelude::*;
 use web_sys::js_sys::JsString;
 
+use catalog::load_catalog;
 use chrono::{DateTime, Local};
+use error::{AskError, AskFailure};
+use gardesh_core::offline::{analyze_offline, refine_offline};
+use gardesh_core::{
+  apply_edits, plan_checked, ChatMessage, CheckedPlan, PlanStop, UnmetConstraint,
+};
 use leptos::prelude::*;
-use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct PlaceInfo {
-  pub place_type: PlaceType,
-  pub tags: Vec<String>,
-}
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
-pub struct PromptAnalyses {
-  // entry_point: Option<Location>,
-  pub place_infos: Vec<PlaceInfo>,
-  pub total_count: Option<u32>,
-}
-
-#[derive(Hash, Eq, Debug, Serialize, Deserialize, Clone)]
-pub struct PlaceScoring {
-  place: Place,
-  score: usize,
-}
-
-impl PartialEq for PlaceScoring {
-  fn eq(&self, other: &Self) -> bool {
-    self.place == other.place && self.score == other.score
-  }
-}
-#[derive(
-  Debug,
-  Serialize,
-  Deserialize,
-  PartialEq,
-  Eq,
-  Hash,
-  Copy,
-  Display,
-  Clone,
-  EnumString,
-  VariantArray,
-  // VariantNames,
-)]
-pub enum PlaceType {
-  #[serde(rename = "موزه")]
-  #[strum(to_string = "موزه")]
-  Museum,
-  #[serde(rename = "مکان تاریخی")]
-  #[strum(to_string = "مکان تاریخی")]
-  Historical,
-  #[serde(rename = "رستوران")]
-  #[strum(to_string = "رستوران")]
-  Restaurant,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Location {
-  pub x: f64,
-  pub y: f64,
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Place {
-  pub title: String,
-  pub category: String,
-  pub r#type: PlaceType,
-  pub region: String,
-  #[serde(default)]
-  pub neighbourhood: String,
-  pub location: Location,
-  #[serde(default)]
-  pub tags: Vec<String>,
-}
-
-impl Eq for Place {}
-
-impl Hash for Place {
-  fn hash<H: Hasher>(&self, state: &mut H) {
-    self.title.hash(state);
-    self.r#type.hash(state);
-  }
-}
-
-impl PartialEq for Place {
-  fn eq(&self, other: &Self) -> bool {
-    self.title == other.title && self.r#type == other.r#type
-  }
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone, Default)]
-pub struct NeshanDataModel {
-  pub tag_pool: Vec<String>,
-  pub items: Vec<Place>,
-}
+pub use gardesh_core::{
+  Location, NeshanDataModel, Place, PlaceId, PlaceInfo, PlaceScoring, PlaceType, PromptAnalyses,
+};
 
 #[derive(Default, Store)]
 pub struct State {
@@ -157,6 +72,12 @@ pub struct Session {
   pub date_created: DateTime<Local>,
   pub title: String,
   pub suggestions: Vec<Suggestion>,
+  /// Set when the last prompt failed; cleared by the next successful answer.
+  pub failure: Option<AskFailure>,
+  /// Prompts and the plans they produced, sent along with follow-ups.
+  pub history: Vec<ChatMessage>,
+  /// Constraints of the prompt the current plan could not honor.
+  pub unmet: Vec<UnmetConstraint>,
   // #[store(skip)]
   // selected_suggestion: Option<Field<Suggestion>>,
 }
@@ -167,6 +88,9 @@ impl Debug for Session {
       .field("date_created", &self.date_created)
       .field("title", &self.title)
       .field("suggestions", &self.suggestions)
+      .field("failure", &self.failure)
+      .field("history", &self.history)
+      .field("unmet", &self.unmet)
       // .field("selected_session", &"Not Implemented")
       .finish()
   }
@@ -180,6 +104,23 @@ impl Debug for Session {
 //   }
 // }
 
+impl Session {
+  /// Ids of the places currently shown for this session, in stop order.
+  pub fn place_ids(&self) -> Vec<PlaceId> {
+    self.suggestions.iter().map(|f| f.selected_place.id.clone()).collect()
+  }
+
+  /// The selected place of every suggestion, in stop order.
+  pub fn stops(&self) -> Vec<Place> {
+    self.suggestions.iter().map(|f| f.selected_place.clone()).collect()
+  }
+
+  /// The suggestions as the plan a follow-up edits, with the places the user picked.
+  pub fn plan(&self) -> Vec<PlanStop> {
+    self.suggestions.iter().map(PlanStop::from).collect()
+  }
+}
+
 impl Hash for Session {
   fn hash<H: Hasher>(&self, state: &mut H) {
     self.date_created.hash(state);
@@ -194,30 +135,30 @@ impl PartialEq for Session {
 
 #[derive(Debug, Store, PartialEq, Eq, Hash, Clone)]
 pub struct Suggestion {
+  info: PlaceInfo,
   places: Vec<Place>,
   selected_place: Place,
 }
 pub trait SuggestionExt {
   fn next(&self);
   fn prev(&self);
+  fn selected_index(&self) -> usize;
 }
 impl SuggestionExt for Field<Suggestion> {
+  fn selected_index(&self) -> usize {
+    let selected_id = self.selected_place().with(|s| s.id.clone());
+    self.places().with(|p| p.iter().position(|f| f.id == selected_id)).unwrap_or_default()
+  }
   fn next(&self) {
-    let index = self
-      .places()
-      .with(|p| p.iter().position(|f| self.selected_place().with(|s| *s == *f)).unwrap())
-      as isize;
+    let index = self.selected_index() as isize;
     let len = self.places().with(Vec::len);
-    let new_index = ((index + 1) % len as isize) as usize % len;
+    let new_index = (index + 1).rem_euclid(len as isize) as usize;
     self.selected_place().set(self.places().with(|f| f[new_index].clone()));
   }
   fn prev(&self) {
-    let index = self
-      .places()
-      .with(|p| p.iter().position(|f| self.selected_place().with(|s| *s == *f)).unwrap())
-      as isize;
+    let index = self.selected_index() as isize;
     let len = self.places().with(Vec::len);
-    let new_index = ((index - 1) % len as isize) as usize % len;
+    let new_index = (index - 1).rem_euclid(len as isize) as usize;
     self.selected_place().set(self.places().with(|f| f[new_index].clone()));
   }
 }
@@ -236,6 +177,8 @@ extern "C" {
   type Marker;
   #[wasm_bindgen(constructor, js_namespace = nmp_mapboxgl)]
   fn newMarker() -> Marker;
+  #[wasm_bindgen(constructor, js_namespace = nmp_mapboxgl)]
+  fn newMarkerWithOptions(options: &JsValue) -> Marker;
   #[wasm_bindgen(method, js_namespace = nmp_mapboxgl)]
   fn setLngLat(this: &Marker, lng_lat: &JsValue) -> Marker;
   #[wasm_bindgen(method, js_namespace = nmp_mapboxgl)]
@@ -251,260 +194,50 @@ extern "C" {
 
 }
 
-async fn ask_ai(prompt: String) -> Vec<Suggestion> {
-  let neshan_history = serde_json::from_str::<NeshanDataModel>(include_str!(
-    "taged_items/neshan_history_results_unique_with_tags.json"
-  ))
-  .unwrap();
-  let neshan_museum = serde_json::from_str::<NeshanDataModel>(include_str!(
-    "taged_items/neshan_museum_results_unique_with_tags.json"
-  ))
-  .unwrap();
-  let neshan_restaurant = serde_json::from_str::<NeshanDataModel>(include_str!(
-    "taged_items/neshan_restaurant_results_unique_with_tags.json"
-  ))
-  .unwrap();
-
-  let system_prompt = format!("
-    درخواست کاربر را تجزیه تحلیل کن.
-    هر نوع مکان ذکر شده را شناسایی کن که یکی از این سه نوع است: موزه، رستوران، مکان تاریخی.
-    سپس برای هر مکان، از بین لیست تگ های زیر، مرتبط ترین موارد به درخواست کاربر را انتخاب کن.
-    اگر کاربر در درخواست خود تعداد مکان هایی که میخواهد ببیند را ذکر کرد، آن را هم در متغیر total_cont بیاور.
-    لیست تگ های مکان های تاریخی:[\n{}]\n
-    لیست تگ های موزه ها:[\n{}]\n
-    لیست تگ های رستوران ها:[\n{}]\n",
-    neshan_history.tag_pool.join("\n"),
-    neshan_museum.tag_pool.join("\n"),
-    neshan_restaurant.tag_pool.join("\n"),
-  );
-  // -- Build an auth_resolver and the AdapterConfig
-  let target_resolver = ServiceTargetResolver::from_resolver_fn(
-    |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
-      let ServiceTarget { model, .. } = service_target;
-      let endpoint = Endpoint::from_static("https://generativelanguage.googleapis.com/v1beta/");
-      let auth = AuthData::from_single(dotenv!("GEMINI_API_KEY"));
-      let model = ModelIden::new(AdapterKind::Gemini, model.model_name);
-      Ok(ServiceTarget { endpoint, auth, model })
-    },
-  );
-
-  // -- Build the new client with this client_config
-  let client = Client::builder()
-    .with_config(ClientConfig::default().with_chat_options(
-      ChatOptions::default().with_response_format(ChatResponseFormat::JsonSpec(JsonSpec::new(
-        "items",
-        json!(
-          {
-            "type": "object",
-            "properties": {
-              "place_infos": {
-                "type": "array",
-                "items": {
-                  "type": "object",
-                  "properties": {
-                    "tags": {
-                      "type": "array",
-                      "items": {
-                        "type": "string"
-                      }
-                    },
-                    "place_type": {
-                      "type": "string",
-                      "enum": [
-                        "موزه",
-                        "مکان تاریخی",
-                        "رستوران"
-                      ]
-                    }
-                  }
-                }
-              },
-              "total_count": {
-                "type": "integer"
-              }
-            },
-            "required": [
-              "place_infos"
-            ]
-          }
-        ),
-      ))), // .with_temperature(0.0)
-           // .with_top_p(0.99),
-    ))
-    .with_service_target_resolver(target_resolver)
-    .build();
-  // -- Build the chat request
-  let chat_req =
-    ChatRequest::new(vec![ChatMessage::system(system_prompt), ChatMessage::user(prompt)]);
-  println!("Question:\n{:#?}", chat_req);
-
-  // -- Build the chat request options (used per execution chat)
-  let chat_res = client
-    .exec_chat(
-      // "gemini-1.5-pro",
-      "gemini-1.5-flash-8b",
-      chat_req.clone(),
-      Some(&ChatOptions::default().with_max_tokens(1000)),
-    )
-    .await
-    .unwrap();
-  console_log(&format!("{:#?}", chat_res));
-  ///////
-
-  // use vec_embed_store::{EmbeddingEngineOptions, EmbeddingsDb, SimilaritySearch, TextChunk};
-
-  let prompt_analyse =
-    serde_json::from_str::<PromptAnalyses>(chat_res.content_text_as_str().unwrap()).unwrap();
-  println!("🟣🟣🟣\n{prompt_analyse:#?}\n🟣🟣🟣");
-  // let neshan_history = serde_json::from_str::<Model>(include_str!(
-  //   "taged_items/neshan_history_results_unique_with_tags.json"
-  // ))
-  // .unwrap();
-  // let neshan_museum = serde_json::from_str::<Model>(include_str!(
-  //   "taged_items/neshan_museum_results_unique_with_tags.json"
-  // ))
-  // .unwrap();
-  // let neshan_restaurant = serde_json::from_str::<Model>(include_str!(
-  //   "taged_items/neshan_restaurant_results_unique_with_tags.json"
-  // ))
-  // .unwrap();
-  // let prompt_analyse = PromptAnalyse {
-  //   place_infos: vec![
-  //     PlaceInfo {
-  //       place_type: PlaceType::Historical,
-  //       tags: vec!["دوره صفویه".to_string(), "دوره تاریخی نامشخص".to_string()],
-  //     },
-  //     PlaceInfo {
-  //       place_type: PlaceType::Restaurant, tags: vec!["دیزی سرای سید".to_string()]
-  //     },
-  //     PlaceInfo { place_type: PlaceType::Museum, tags: vec!["ساعت".to_string()] },
-  //   ],
-  //   total_count: Some(2),
-  // };
-  // neshan_history.items.swap_remove(neshan_history.items.iter().)
-  let all_places = {
-    let mut tmp = Vec::with_capacity(
-      neshan_history.items.len() + neshan_museum.items.len() + neshan_restaurant.items.len(),
-    );
-    tmp.extend(neshan_history.items);
-    tmp.extend(neshan_museum.items);
-    tmp.extend(neshan_restaurant.items);
-    tmp
-  };
-
-  prompt_analyse
-    .place_infos
-    .iter()
-    .zip(repeat(&all_places))
-    .map(|(info, all_places)| {
-      all_places
-        .into_iter()
-        .filter(|place| {
-          place.r#type == info.place_type && place.tags.iter().any(|tag| info.tags.contains(tag))
-        })
-        .cloned()
-        // .map(|place| PlaceScoring {
-        //   score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-        //   place: place.clone(),
-        // })
-        .collect_vec()
-    })
-    .map(|f| Suggestion { selected_place: f[0].clone(), places: f })
-    .collect_vec()
-
-  // let mut res = prompt_analyse
-  //   .place_infos
-  //   .into_iter()
-  //   .map(|info| {
-  //     // println!("{info:#?}");
-  //     .filter(|place_scoring| place_scoring.score > 0)
-  //     .map(|place| PlaceScoring {
-  //           score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-  //           place: place.clone(),
-  //         })
-  //     match info.place_type {
-  //       PlaceType::Museum => neshan_museum
-  //         .items
-  //         .iter()
-  //         .map(|place| PlaceScoring {
-  //           score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-  //           place: place.clone(),
-  //         })
-  //         .filter(|place_scoring| place_scoring.score > 0)
-  //         .collect_vec(),
-  //       PlaceType::Historical => neshan_history
-  //         .items
-  //         .iter()
-  //         .map(|place| PlaceScoring {
-  //           score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-  //           place: place.clone(),
-  //         })
-  //         .filter(|place_scoring| place_scoring.score > 0)
-  //         .collect_vec(),
-  //       PlaceType::Restaurant => neshan_restaurant
-  //         .items
-  //         .iter()
-  //         .map(|place| PlaceScoring {
-  //           score: place.tags.iter().filter(|tag| info.tags.contains(tag)).count(),
-  //           place: place.clone(),
-  //         })
-  //         .filter(|place_scoring| place_scoring.score > 0)
-  //         .collect_vec(),
-  //     }
-  //   })
-  //   .filter(|palce_scoring| palce_scoring.len() > 0)
-  //   .collect_vec();
-  // // println!("{res:#?}");
-  // res
-  //   .into_iter()
-  //   .inspect(|f| println!("🟡{f:#?}"))
-  //   .multi_cartesian_product()
-  //   .inspect(|f| println!("🙏{f:#?}"))
-  //   .sorted_by(|a, b| {
-  //     let max_distance = 20.0; // km
-
-  //     let dist_a = (0..a.len())
-  //       .map(|i| distance_haversine(&a[i].place.location, &a[(i + 1) % a.len()].place.location))
-  //       .sum::<f64>();
-  //     let dist_b = (0..b.len())
-  //       .map(|i| distance_haversine(&b[i].place.location, &b[(i + 1) % b.len()].place.location))
-  //       .sum::<f64>();
-
-  //     // Normalize distances to 0-1 scale
-  //     let norm_dist_a = dist_a / max_distance;
-  //     let norm_dist_b = dist_b / max_distance;
-
-  //     // Normalize scores to 0-1 scale (assuming higher score is better)
-  //     let score_a =
-  //       a.iter().map(|item| (item.score - 1) as f64 / 3.0).sum::<f64>() / a.len() as f64;
-  //     let score_b =
-  //       b.iter().map(|item| (item.score - 1) as f64 / 3.0).sum::<f64>() / b.len() as f64;
-
-  //     // Combine normalized distance and score. Here we use subtraction because lower distance and higher score are better.
-  //     // If you want to make distance and score equally important, you might adjust the weights.
-  //     let combined_a = (0.7 * norm_dist_a) - (0.3 * score_a);
-  //     let combined_b = (0.7 * norm_dist_b) - (0.3 * score_b);
-  //     combined_a.partial_cmp(&combined_b).unwrap_or(Equal)
-  //   })
-  //   .next()
-  //   .unwrap()
-  //   .into_iter()
-  //   .map(|place_scoring| place_scoring.place)
-  //   .collect::<Vec<Place>>()
-  // panic!("HEEEEY");
-  // println!("✅✅✅✅\n{final_places:#?}\n✅✅✅✅");
-  // final_places
+impl From<PlanStop> for Suggestion {
+  fn from(stop: PlanStop) -> Self {
+    // `plan` puts the chosen place first and never returns a stop without candidates.
+    Suggestion {
+      info: stop.info,
+      selected_place: stop.candidates[0].clone(),
+      places: stop.candidates,
+    }
+  }
 }
-fn _distance_haversine(loc1: &Location, loc2: &Location) -> f64 {
-  let r = 6371e3; // Earth's radius in meters
-  let phi1 = loc1.y.to_radians();
-  let phi2 = loc2.y.to_radians();
-  let delta_phi = (loc2.y - loc1.y).to_radians();
-  let delta_lambda = (loc2.x - loc1.x).to_radians();
 
-  let a =
-    (delta_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
-  let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+impl From<&Suggestion> for PlanStop {
+  fn from(suggestion: &Suggestion) -> Self {
+    let others = suggestion.places.iter().filter(|f| **f != suggestion.selected_place);
+    PlanStop {
+      info: suggestion.info.clone(),
+      candidates: std::iter::once(&suggestion.selected_place).chain(others).cloned().collect(),
+    }
+  }
+}
 
-  r * c
+/// Plans `prompt`, or, when the session already has a plan (`stops`), applies it as an edit to
+/// that plan. Only a fresh plan reports unmet constraints.
+async fn ask_ai(
+  prompt: String,
+  history: Vec<ChatMessage>,
+  stops: Vec<PlanStop>,
+) -> Result<CheckedPlan, AskError> {
+  if backend::is_tauri() {
+    return match stops.is_empty() {
+      true => backend::plan(&prompt).await,
+      false => backend::refine(&prompt, &history, &stops)
+        .await
+        .map(|stops| CheckedPlan { stops, ..Default::default() }),
+    };
+  }
+  // A plain browser has no backend holding an API key, so the prompt is analyzed in the page.
+  let catalog = load_catalog().await?;
+  if stops.is_empty() {
+    let prompt_analyse = analyze_offline(&catalog, &prompt);
+    console_log(&format!("{prompt_analyse:?}"));
+    return Ok(plan_checked(&catalog, &prompt_analyse)?);
+  }
+  let edits = refine_offline(&catalog, &stops, &prompt);
+  console_log(&format!("{edits:?}"));
+  Ok(CheckedPlan { stops: apply_edits(&catalog, &stops, &edits)?, ..Default::default() })
 }